@@ -8,7 +8,7 @@ fn main() {
     let cpp_17_flag = "/std:c++17";
     #[cfg(not(all(target_os = "windows", target_env = "msvc")))]
     let cpp_17_flag = "-std=c++17";
-    
+
     cxx_build::bridge("src/lib.rs")
         .include(Path::new("CTranslate2/include"))
         .flag_if_supported(cpp_17_flag)
@@ -49,7 +49,6 @@ fn main() {
         println!("cargo:rustc-link-lib=framework=Accelerate");
     }
     #[cfg(feature = "accelerate")]
-    
     #[cfg(not(feature = "accelerate"))]
     cmd.arg("-DWITH_ACCELERATE=OFF");
 
@@ -80,7 +79,7 @@ fn main() {
                 println!("cargo:rustc-link-search=native={}", path.display());
             }
         };
-    
+
         println!("cargo:rustc-link-lib=dylib=cudart_static");
         println!("cargo:rerun-if-env-changed=CUDA_LIBRARY_PATH");
     }
@@ -149,4 +148,3 @@ fn main() {
     _ = env::set_current_dir("../");
     _ = std::fs::remove_dir_all("build");
 }
-
@@ -0,0 +1,63 @@
+use clap::Parser;
+use ctranslate2_rs::{encrypt_model, generate_key};
+use std::fs::OpenOptions;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Path to converted CTranslate2 model
+    model_path: PathBuf,
+
+    /// Directory to write the encrypted model to
+    output_path: PathBuf,
+
+    /// File holding the 32-byte key as hex, created with a new key if it does not exist
+    #[arg(short, long)]
+    key_file: PathBuf,
+}
+
+fn parse_key(hex: &str) -> Option<[u8; 32]> {
+    let hex = hex.trim();
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    let mut key = [0u8; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(key)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    let key = if args.key_file.exists() {
+        let hex = std::fs::read_to_string(&args.key_file)?;
+        parse_key(&hex).ok_or("key file must contain 64 hexadecimal characters")?
+    } else {
+        let key = generate_key();
+        let hex: String = key.iter().map(|byte| format!("{byte:02x}")).collect();
+        // Only readable by the owner, and never replaces an existing file.
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        options
+            .open(&args.key_file)?
+            .write_all((hex + "\n").as_bytes())?;
+        println!("Wrote new key to {}", args.key_file.display());
+        key
+    };
+
+    encrypt_model(
+        &args.model_path.to_string_lossy(),
+        &args.output_path.to_string_lossy(),
+        &key,
+    )?;
+    println!("Wrote encrypted model to {}", args.output_path.display());
+    Ok(())
+}
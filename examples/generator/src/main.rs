@@ -1,15 +1,13 @@
 use clap::Parser;
 use colored::*;
-use ctranslate2_rs::{
-    ffi, BatchType, ComputeType, Device, GenerationOptions, GenerationStepResult, Generator,
-};
+use ctranslate2_rs::{ComputeType, Device, GenerationOptions, Generator};
+use futures::StreamExt;
 use std::{
     io::{stdout, Write},
     path::PathBuf,
     time::Instant,
 };
 use tokenizers::{tokenizer::Tokenizer, FromPretrainedParameters};
-use tokio::sync::mpsc;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -166,7 +164,7 @@ async fn main() {
     let tokenized = tokenizer
         .encode(prompt.clone(), args.add_special_tokens)
         .unwrap()
-        .get_tokens()
+        .get_ids()
         .to_vec();
     let num_prompt_tokens = tokenized.len();
 
@@ -192,46 +190,26 @@ async fn main() {
         let config: serde_json::Value =
             serde_json::from_str(&std::fs::read_to_string(config_path).unwrap()).unwrap();
         let eos_token = config.get("eos_token").unwrap().as_str().unwrap();
-        options.suppress_sequences = ffi::new_vec_vec_string();
-        options
-            .suppress_sequences
-            .as_mut()
-            .unwrap()
-            .push_back(vec![eos_token.to_string()]);
+        options.suppress_sequences = vec![vec![eos_token.to_string()]];
     }
 
     print!("{}", prompt.yellow());
 
-    let (tx, mut rx) = mpsc::channel::<usize>(args.max_new_tokens);
-
-    tokio::task::spawn(async move {
-        while let Some(token) = rx.recv().await {
-            if let Ok(decoded) = tokenizer.decode(vec![token as u32], true) {
-                print!("{decoded}");
-                stdout().flush().unwrap();
-            }
-        }
-    });
-
     let start = Instant::now();
-    let result = tokio::task::spawn_blocking(move || {
-        generator.generate_batch(
-            vec![tokenized],
-            1,
-            BatchType::Examples,
-            options,
-            Some(move |result: GenerationStepResult| {
-                let _ = tx.try_send(result.token_id);
-                false
-            }),
-        )
-    })
-    .await
-    .unwrap()
-    .unwrap();
+    let mut num_generated_tokens = 0;
+    let mut steps = generator.generate_tokens_stream_ids(tokenized, options);
+    while let Some(step) = steps.next().await {
+        if let Ok(decoded) = tokenizer.decode(vec![step.token_id as u32], true) {
+            print!("{decoded}");
+            stdout().flush().unwrap();
+        }
+        num_generated_tokens += 1;
+    }
+    if let Some(error) = steps.take_error() {
+        panic!("Generation failed: {error:?}");
+    }
     let duration = start.elapsed();
 
-    let num_generated_tokens = result[0].sequence_ids.at(0).unwrap().len() - num_prompt_tokens;
     let tokens_per_second = num_generated_tokens as f32 / duration.as_secs_f32();
 
     if !args.quiet {
@@ -0,0 +1,71 @@
+use clap::Parser;
+use ctranslate2_rs::model_spec::ModelInfo;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Path to converted CTranslate2 model
+    model_path: PathBuf,
+
+    /// Do not list the variables
+    #[arg(long, default_value_t = false)]
+    summary: bool,
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024. && unit + 1 < UNITS.len() {
+        value /= 1024.;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    let info = ModelInfo::from_model_dir(&args.model_path)?;
+    println!("Spec: {} (revision {})", info.spec, info.revision);
+    println!("Binary version: {}", info.binary_version);
+    println!(
+        "Variables: {} ({})",
+        info.variables.len(),
+        format_bytes(info.num_bytes())
+    );
+
+    if !args.summary {
+        let width = info
+            .variables
+            .iter()
+            .map(|variable| variable.name.len())
+            .max()
+            .unwrap_or(0);
+        for variable in &info.variables {
+            println!(
+                "  {:width$}  {:8}  {:?}",
+                variable.name,
+                variable.dtype.to_string(),
+                variable.shape,
+            );
+        }
+    }
+
+    if !info.aliases.is_empty() {
+        println!("Aliases:");
+        for (alias, variable) in &info.aliases {
+            println!("  {alias} -> {variable}");
+        }
+    }
+
+    let config_path = args.model_path.join("config.json");
+    if config_path.exists() {
+        let config: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(config_path)?)?;
+        println!("Config:");
+        println!("{}", serde_json::to_string_pretty(&config)?);
+    }
+    Ok(())
+}
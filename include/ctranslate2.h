@@ -1,21 +1,106 @@
 #pragma once
 
+#include <algorithm>
 #include <chrono>
+#include <cstring>
 #include <future>
+#include <istream>
+#include <stdexcept>
+#include <streambuf>
 #include <variant>
 #include <string>
 #include <vector>
 
 #include "rust/cxx.h"
 
+#include "ctranslate2/devices.h"
 #include "ctranslate2/replica_pool.h"
 #include "ctranslate2/generator.h"
+#include "ctranslate2/translator.h"
+#include "ctranslate2/encoder.h"
+#include "ctranslate2/models/whisper.h"
+
+// cxx only forwards the message of an exception to Rust. The type of the last exception
+// converted on each thread is kept here so that the Rust side can tell invalid inputs and
+// allocation failures apart from other errors.
+inline std::string &last_exception_kind_storage()
+{
+    thread_local std::string kind;
+    return kind;
+}
+
+inline rust::String last_exception_kind()
+{
+    return rust::String(last_exception_kind_storage());
+}
+
+// Exception raised while loading a model, whose kind tells the Rust side what failed.
+class LoadException : public std::runtime_error
+{
+public:
+    LoadException(const char *kind, const std::string &message)
+        : std::runtime_error(message), _kind(kind)
+    {
+    }
+
+    const char *kind() const noexcept
+    {
+        return _kind;
+    }
+
+private:
+    const char *_kind;
+};
+
+namespace rust
+{
+    namespace behavior
+    {
+        template <typename Try, typename Fail>
+        static void trycatch(Try &&func, Fail &&fail) noexcept
+        try
+        {
+            func();
+        }
+        catch (const LoadException &e)
+        {
+            last_exception_kind_storage() = e.kind();
+            fail(e.what());
+        }
+        catch (const std::bad_alloc &e)
+        {
+            last_exception_kind_storage() = "bad_alloc";
+            fail(e.what());
+        }
+        catch (const std::invalid_argument &e)
+        {
+            last_exception_kind_storage() = "invalid_argument";
+            fail(e.what());
+        }
+        catch (const std::out_of_range &e)
+        {
+            last_exception_kind_storage() = "out_of_range";
+            fail(e.what());
+        }
+        catch (const std::exception &e)
+        {
+            last_exception_kind_storage() = "exception";
+            fail(e.what());
+        }
+    }
+}
 
 class GeneratorWrapper;
+class TranslatorWrapper;
+class EncoderWrapper;
+class WhisperWrapper;
+class StorageViewWrapper;
+class GenerationAsyncResult;
 template <class CPPType, class RustType>
 class VecVec;
 typedef VecVec<std::string, rust::String> VecVecString;
 typedef VecVec<size_t, size_t> VecVecUsize;
+typedef VecVec<float, float> VecVecF32;
 #include "ctranslate2-rs/src/lib.rs.h"
 
 template <class FromVectorType, class ToVectorType>
@@ -75,6 +160,7 @@ public:
     size_t len() const { return mData.size(); }
     bool empty() const { return mData.empty(); }
     const DataType &data() const { return mData; }
+    DataType release() { return std::move(mData); }
 
 private:
     DataType mData;
@@ -90,6 +176,72 @@ std::unique_ptr<VecVecUsize> new_vec_vec_usize()
     return std::make_unique<VecVecUsize>(VecVecUsize());
 }
 
+std::unique_ptr<VecVecF32> new_vec_vec_f32()
+{
+    return std::make_unique<VecVecF32>(VecVecF32());
+}
+
+class StorageViewWrapper
+{
+public:
+    StorageViewWrapper(ctranslate2::StorageView view)
+        : _view(std::move(view))
+    {
+    }
+
+    rust::Vec<size_t> shape() const
+    {
+        rust::Vec<size_t> ret;
+        for (const auto dim : _view.shape())
+            ret.push_back((size_t)dim);
+        return ret;
+    }
+
+    rust::String dtype() const
+    {
+        return ctranslate2::dtype_name(_view.dtype());
+    }
+
+    rust::String device() const
+    {
+        return ctranslate2::device_to_str(_view.device());
+    }
+
+    rust::Vec<float> to_vec_f32() const
+    {
+        ctranslate2::StorageView view = _view.device() == ctranslate2::Device::CPU
+                                            ? _view
+                                            : _view.to(ctranslate2::Device::CPU);
+        if (view.dtype() == ctranslate2::DataType::FLOAT16)
+            view = view.to_float();
+        else if (view.dtype() != ctranslate2::DataType::FLOAT)
+            throw std::invalid_argument("Cannot convert a " + ctranslate2::dtype_name(view.dtype()) +
+                                        " storage to float32");
+        return ConvertVector<std::vector<float>, rust::Vec<float>>(view.to_vector<float>());
+    }
+
+    const ctranslate2::StorageView &view() const { return _view; }
+
+private:
+    ctranslate2::StorageView _view;
+};
+
+std::unique_ptr<StorageViewWrapper> new_storage_view(rust::Slice<const size_t> shape, rust::Vec<float> data)
+{
+    ctranslate2::Shape converted;
+    size_t size = 1;
+    for (const auto dim : shape)
+    {
+        converted.push_back((ctranslate2::dim_t)dim);
+        size *= dim;
+    }
+    if (size != data.size())
+        throw std::invalid_argument("Expected " + std::to_string(size) + " values for the given shape, got " +
+                                    std::to_string(data.size()));
+    return std::make_unique<StorageViewWrapper>(ctranslate2::StorageView(
+        std::move(converted), ConvertVector<rust::Vec<float>, std::vector<float>>(std::move(data))));
+}
+
 class ComputeTypeResolver
 {
 private:
@@ -177,6 +329,11 @@ private:
 template <typename Result>
 std::vector<Result> wait_on_futures(std::vector<std::future<Result>> futures)
 {
+    // Wait for every job before collecting the results, so that none of them is still running
+    // (and possibly calling back into Rust) when an exception is rethrown.
+    for (auto &future : futures)
+        future.wait();
+
     std::vector<Result> results;
     results.reserve(futures.size());
     for (auto &future : futures)
@@ -202,6 +359,172 @@ maybe_wait_on_futures(std::vector<std::future<Result>> futures, bool asynchronou
     }
 }
 
+// Stream over bytes borrowed from Rust, which avoids copying the in-memory model files once more.
+class ByteStreamBuf : public std::streambuf
+{
+public:
+    ByteStreamBuf(rust::Slice<const uint8_t> data)
+    {
+        char *begin = const_cast<char *>(reinterpret_cast<const char *>(data.data()));
+        setg(begin, begin, begin + data.size());
+    }
+
+protected:
+    pos_type seekoff(off_type off, std::ios_base::seekdir dir, std::ios_base::openmode) override
+    {
+        char *base = dir == std::ios_base::beg ? eback() : dir == std::ios_base::cur ? gptr() : egptr();
+        if (off < eback() - base || off > egptr() - base)
+            return pos_type(off_type(-1));
+        setg(eback(), base + off, egptr());
+        return pos_type(gptr() - eback());
+    }
+
+    pos_type seekpos(pos_type pos, std::ios_base::openmode which) override
+    {
+        return seekoff(off_type(pos), std::ios_base::beg, which);
+    }
+
+};
+
+class ByteStream : public std::istream
+{
+public:
+    ByteStream(rust::Slice<const uint8_t> data)
+        : std::istream(nullptr), _buffer(data)
+    {
+        rdbuf(&_buffer);
+    }
+
+private:
+    ByteStreamBuf _buffer;
+};
+
+// Stream reading a model file from a Rust ModelFileReader in chunks, so that the file is never
+// held in memory in full. Large reads, such as the model variables, bypass the buffer.
+class ModelFileStreamBuf : public std::streambuf
+{
+public:
+    ModelFileStreamBuf(std::shared_ptr<rust::Box<ModelReaderContext>> reader,
+                       rust::Box<ModelFileReader> file)
+        : _reader(std::move(reader)), _file(std::move(file)), _buffer(1 << 16)
+    {
+        setg(_buffer.data(), _buffer.data(), _buffer.data());
+    }
+
+protected:
+    int_type underflow() override
+    {
+        if (gptr() == egptr())
+        {
+            size_t size = read(_buffer.data(), _buffer.size());
+            setg(_buffer.data(), _buffer.data(), _buffer.data() + size);
+        }
+        return gptr() == egptr() ? traits_type::eof() : traits_type::to_int_type(*gptr());
+    }
+
+    std::streamsize xsgetn(char *s, std::streamsize count) override
+    {
+        std::streamsize total = 0;
+        while (total < count)
+        {
+            if (gptr() == egptr() && count - total >= (std::streamsize)_buffer.size())
+            {
+                size_t size = read(s + total, count - total);
+                if (size == 0)
+                    break;
+                total += size;
+                continue;
+            }
+            if (underflow() == traits_type::eof())
+                break;
+            std::streamsize size = std::min<std::streamsize>(egptr() - gptr(), count - total);
+            std::memcpy(s + total, gptr(), size);
+            gbump((int)size);
+            total += size;
+        }
+        return total;
+    }
+
+private:
+    size_t read(char *data, size_t size)
+    {
+        return _file->read(rust::Slice<uint8_t>(reinterpret_cast<uint8_t *>(data), size));
+    }
+
+    // The file may borrow from the reader, so it is declared after it to be dropped first.
+    std::shared_ptr<rust::Box<ModelReaderContext>> _reader;
+    rust::Box<ModelFileReader> _file;
+    std::vector<char> _buffer;
+};
+
+class ModelFileStream : public std::istream
+{
+public:
+    ModelFileStream(std::shared_ptr<rust::Box<ModelReaderContext>> reader,
+                    rust::Box<ModelFileReader> file)
+        : std::istream(nullptr), _buffer(std::move(reader), std::move(file))
+    {
+        rdbuf(&_buffer);
+        // Rethrows the errors of the Rust reader instead of only setting badbit.
+        exceptions(std::ios_base::badbit);
+    }
+
+private:
+    ModelFileStreamBuf _buffer;
+};
+
+// Reads the model files from an implementation of the Rust ModelReader trait.
+class RustModelReader : public ctranslate2::models::ModelReader
+{
+public:
+    RustModelReader(rust::Box<ModelReaderContext> reader)
+        : _reader(std::make_shared<rust::Box<ModelReaderContext>>(std::move(reader)))
+    {
+    }
+
+    std::string get_model_id() const override
+    {
+        return (std::string)(*_reader)->model_id();
+    }
+
+    std::unique_ptr<std::istream> get_file(const std::string &filename, const bool = false) override
+    {
+        rust::Box<ModelFileReader> file = (*_reader)->open_file(filename);
+        if (!file->found())
+            return nullptr;
+        return std::make_unique<ModelFileStream>(_reader, std::move(file));
+    }
+
+private:
+    std::shared_ptr<rust::Box<ModelReaderContext>> _reader;
+};
+
+// Reads in-memory model files in place, where ModelMemoryReader would copy them into strings.
+class RustFilesReader : public ctranslate2::models::ModelReader
+{
+public:
+    RustFilesReader(rust::Vec<ModelFile> files)
+        : _files(std::move(files))
+    {
+    }
+
+    std::string get_model_id() const override
+    {
+        return "memory";
+    }
+
+    std::unique_ptr<std::istream> get_file(const std::string &filename, const bool = false) override
+    {
+        for (const auto &file : _files)
+            if (std::string(file.name) == filename)
+                return std::make_unique<ByteStream>(rust::Slice<const uint8_t>(file.content.data(), file.content.size()));
+        return nullptr;
+    }
+
+private:
+    rust::Vec<ModelFile> _files;
+};
+
 template <typename T>
 class ReplicaPoolHelper
 {
@@ -213,17 +536,74 @@ public:
                       size_t inter_threads,
                       size_t intra_threads,
                       int max_queued_batches)
-        : _model_loader(std::make_shared<ctranslate2::models::ModelFileReader>(model_path))
+        : ReplicaPoolHelper(std::make_shared<ctranslate2::models::ModelFileReader>(model_path),
+                            device,
+                            device_indices,
+                            compute_type,
+                            inter_threads,
+                            intra_threads,
+                            max_queued_batches)
+    {
+    }
+
+    ReplicaPoolHelper(std::shared_ptr<ctranslate2::models::ModelReader> model_reader,
+                      const std::string &device,
+                      const std::vector<int> &device_indices,
+                      const std::string &compute_type,
+                      size_t inter_threads,
+                      size_t intra_threads,
+                      int max_queued_batches)
+        : _model_loader(std::move(model_reader))
     {
-        _model_loader.device = ctranslate2::str_to_device(device);
+        try
+        {
+            _model_loader.device = ctranslate2::str_to_device(device);
+            _model_loader.compute_type = ctranslate2::str_to_compute_type(compute_type);
+        }
+        catch (const std::exception &e)
+        {
+            throw LoadException("unsupported_device", e.what());
+        }
+        if (ctranslate2::get_device_count(_model_loader.device) == 0)
+            throw LoadException("unsupported_device", "no " + device + " device is available");
         _model_loader.device_indices = device_indices;
-        _model_loader.compute_type = ctranslate2::str_to_compute_type(compute_type);
         _model_loader.num_replicas_per_device = inter_threads;
 
         _pool_config.num_threads_per_replica = intra_threads;
         _pool_config.max_queued_batches = (long)max_queued_batches;
 
-        _pool = std::make_unique<T>(_model_loader, _pool_config);
+        // The exceptions raised while loading are classified by type: CTranslate2 raises
+        // std::invalid_argument when the compute type is not supported by the device and
+        // std::runtime_error when the model files cannot be read, while the errors of the JSON
+        // library, which reads the configuration, only derive from std::exception.
+        try
+        {
+            _pool = std::make_unique<T>(_model_loader, _pool_config);
+            // The weights were copied into the replicas: release the reader, and with it the
+            // memory maps or in-memory files it holds.
+            _model_loader.model_reader.reset();
+        }
+        catch (const std::bad_alloc &)
+        {
+            throw;
+        }
+        catch (const rust::Error &e)
+        {
+            // Raised by a Rust model reader.
+            throw LoadException("model_load", e.what());
+        }
+        catch (const std::invalid_argument &e)
+        {
+            throw LoadException("unsupported_device", e.what());
+        }
+        catch (const std::runtime_error &e)
+        {
+            throw LoadException("model_load", e.what());
+        }
+        catch (const std::exception &e)
+        {
+            throw LoadException("invalid_config", e.what());
+        }
     }
 
     ~ReplicaPoolHelper()
@@ -262,21 +642,85 @@ protected:
     ctranslate2::ReplicaPoolConfig _pool_config;
 };
 
-class GeneratorWrapper : public ReplicaPoolHelper<ctranslate2::Generator>
+static ctranslate2::ScoringOptions ConvertScoringOptions(rust::Box<ScoringOptions> options)
+{
+    ctranslate2::ScoringOptions ret;
+    ret.max_input_length = options->max_input_length;
+    ret.offset = options->offset;
+    return ret;
+}
+
+static rust::Vec<ScoringResult> ConvertScoringResults(std::vector<ctranslate2::ScoringResult> &&results)
+{
+    rust::Vec<ScoringResult> ret;
+    for (auto &result : results)
+        ret.emplace_back(ScoringResult{
+            ConvertVector<std::vector<std::string>, rust::Vec<rust::String>>(std::move(result.tokens)),
+            ConvertVector<std::vector<float>, rust::Vec<float>>(std::move(result.tokens_score))});
+    return ret;
+}
+
+static GenerationResult ConvertGenerationResult(ctranslate2::GenerationResult &&result)
+{
+    return GenerationResult{
+        std::make_unique<VecVecString>(VecVecString(std::move(result.sequences))),
+        std::make_unique<VecVecUsize>(VecVecUsize(std::move(result.sequences_ids))),
+        ConvertVector<std::vector<float>, rust::Vec<float>>(std::move(result.scores))};
+}
+
+class GenerationAsyncResult : public AsyncResult<ctranslate2::GenerationResult>
+{
+public:
+    using AsyncResult::AsyncResult;
+
+    GenerationResult result()
+    {
+        return ConvertGenerationResult(ctranslate2::GenerationResult(AsyncResult::result()));
+    }
+};
+
+// Generator that exposes the submission of jobs to its replicas, so that the results of
+// asynchronous calls are handed to Rust by the worker thread that produced them.
+class GeneratorPool : public ctranslate2::Generator
+{
+public:
+    using ctranslate2::Generator::Generator;
+    using ctranslate2::ReplicaPool<ctranslate2::models::SequenceGeneratorReplica>::post_examples;
+};
+
+class GeneratorWrapper : public ReplicaPoolHelper<GeneratorPool>
 {
 public:
     using ReplicaPoolHelper::ReplicaPoolHelper;
     using CallbackFunction = rust::Fn<bool(GenerationStepResult, GenerateCallbackContext const &)>;
-    using Callback = std::pair<CallbackFunction, rust::Box<GenerateCallbackContext>>;
+    // Step callback given by Rust, wrapped for each batch by _generate_batch_async.
+    struct StepCallback
+    {
+        CallbackFunction callback;
+        const GenerateCallbackContext *context;
+    };
+    using AsyncCallbackFunction = rust::Fn<void(size_t, std::unique_ptr<GenerationAsyncResult>, GenerateAsyncContext const &)>;
+    // Step callback of an asynchronous call, which owns its context.
+    struct AsyncStepCallback
+    {
+        CallbackFunction callback;
+        rust::Box<GenerateCallbackContext> context;
+    };
 
     rust::Vec<GenerationResult> generate_batch(std::unique_ptr<VecVecString> tokens,
                                                size_t max_batch_size,
                                                rust::Str batch_type_str,
                                                rust::Box<GenerationOptions> options) const
     {
-        auto futures = _generate_batch_async(std::move(tokens), max_batch_size, std::move(batch_type_str), ConvertGenerationOptions(std::move(options)));
-        auto results = wait_on_futures(std::move(futures));
-        return ConvertGenerationResults(std::move(results));
+        return _generate_batch(std::move(tokens), max_batch_size, std::move(batch_type_str), ConvertGenerationOptions(std::move(options)), nullptr);
+    }
+
+    rust::Vec<GenerationResult> generate_batch_ids(std::unique_ptr<VecVecUsize> ids,
+                                                   size_t max_batch_size,
+                                                   rust::Str batch_type_str,
+                                                   rust::Box<GenerationOptions> options) const
+    {
+        return _generate_batch(std::move(ids), max_batch_size, std::move(batch_type_str), ConvertGenerationOptions(std::move(options)), nullptr);
     }
 
     rust::Vec<GenerationResult> generate_batch_with_callback(std::unique_ptr<VecVecString> tokens,
@@ -284,64 +728,298 @@ public:
                                                              rust::Str batch_type_str,
                                                              rust::Box<GenerationOptions> options,
                                                              CallbackFunction callback,
-                                                             rust::Box<GenerateCallbackContext> context) const
+                                                             const GenerateCallbackContext &context) const
     {
-        auto converted = ConvertGenerationOptions(std::move(options));
-        converted.callback = [callback, rawContext = context.into_raw()](ctranslate2::GenerationStepResult result) -> bool
-        {
-            GenerationStepResult converted;
-            converted.batch_id = result.batch_id;
-            converted.is_last = result.is_last;
-            converted.log_prob = result.log_prob ? *result.log_prob : 0;
-            converted.log_prob_valid = result.log_prob.has_value();
-            converted.step = result.step;
-            converted.token_id = result.token_id;
-
-            return callback(std::move(converted), *rawContext);
-        };
-
-        auto futures = _generate_batch_async(std::move(tokens), max_batch_size, std::move(batch_type_str), std::move(converted));
+        StepCallback step_callback{callback, &context};
+        return _generate_batch(std::move(tokens), max_batch_size, std::move(batch_type_str), ConvertGenerationOptions(std::move(options)), &step_callback);
+    }
+
+    rust::Vec<GenerationResult> generate_batch_ids_with_callback(std::unique_ptr<VecVecUsize> ids,
+                                                                 size_t max_batch_size,
+                                                                 rust::Str batch_type_str,
+                                                                 rust::Box<GenerationOptions> options,
+                                                                 CallbackFunction callback,
+                                                                 const GenerateCallbackContext &context) const
+    {
+        StepCallback step_callback{callback, &context};
+        return _generate_batch(std::move(ids), max_batch_size, std::move(batch_type_str), ConvertGenerationOptions(std::move(options)), &step_callback);
+    }
+
+    void generate_batch_async(VecVecString &tokens,
+                              size_t max_batch_size,
+                              rust::Str batch_type_str,
+                              rust::Box<GenerationOptions> options,
+                              AsyncCallbackFunction callback,
+                              rust::Box<GenerateAsyncContext> context) const
+    {
+        _post_generation(tokens, max_batch_size, std::move(batch_type_str), std::move(options), nullptr, callback, std::move(context));
+    }
+
+    void generate_batch_async_with_callback(VecVecString &tokens,
+                                            size_t max_batch_size,
+                                            rust::Str batch_type_str,
+                                            rust::Box<GenerationOptions> options,
+                                            CallbackFunction step_callback,
+                                            rust::Box<GenerateCallbackContext> step_context,
+                                            AsyncCallbackFunction callback,
+                                            rust::Box<GenerateAsyncContext> context) const
+    {
+        auto wrapped = std::make_shared<AsyncStepCallback>(AsyncStepCallback{step_callback, std::move(step_context)});
+        _post_generation(tokens, max_batch_size, std::move(batch_type_str), std::move(options), std::move(wrapped), callback, std::move(context));
+    }
+
+    void generate_batch_ids_async_with_callback(VecVecUsize &ids,
+                                                size_t max_batch_size,
+                                                rust::Str batch_type_str,
+                                                rust::Box<GenerationOptions> options,
+                                                CallbackFunction step_callback,
+                                                rust::Box<GenerateCallbackContext> step_context,
+                                                AsyncCallbackFunction callback,
+                                                rust::Box<GenerateAsyncContext> context) const
+    {
+        auto wrapped = std::make_shared<AsyncStepCallback>(AsyncStepCallback{step_callback, std::move(step_context)});
+        _post_generation(ids, max_batch_size, std::move(batch_type_str), std::move(options), std::move(wrapped), callback, std::move(context));
+    }
+
+    rust::Vec<ScoringResult> score_batch(std::unique_ptr<VecVecString> tokens,
+                                         size_t max_batch_size,
+                                         rust::Str batch_type_str,
+                                         rust::Box<ScoringOptions> options) const
+    {
+        return _score_batch(std::move(tokens), max_batch_size, std::move(batch_type_str), std::move(options));
+    }
+
+    rust::Vec<ScoringResult> score_batch_ids(std::unique_ptr<VecVecUsize> ids,
+                                             size_t max_batch_size,
+                                             rust::Str batch_type_str,
+                                             rust::Box<ScoringOptions> options) const
+    {
+        return _score_batch(std::move(ids), max_batch_size, std::move(batch_type_str), std::move(options));
+    }
+
+    std::unique_ptr<StorageViewWrapper> forward_batch(std::unique_ptr<VecVecString> tokens,
+                                                      bool return_log_probs) const
+    {
+        if (!tokens || tokens->empty())
+            throw std::invalid_argument("forward_batch requires at least one input");
+
+        auto future = _pool->forward_batch_async(tokens->data(), return_log_probs);
+        return std::make_unique<StorageViewWrapper>(future.get());
+    }
+
+private:
+    template <typename Tokens>
+    rust::Vec<GenerationResult> _generate_batch(std::unique_ptr<Tokens> tokens,
+                                                size_t max_batch_size,
+                                                rust::Str batch_type_str,
+                                                ctranslate2::GenerationOptions &&options,
+                                                const StepCallback *step_callback) const
+    {
+        auto futures = _generate_batch_async(std::move(tokens), max_batch_size, std::move(batch_type_str), std::move(options), step_callback);
         auto results = wait_on_futures(std::move(futures));
         return ConvertGenerationResults(std::move(results));
     }
 
-private:
-    std::vector<std::future<ctranslate2::GenerationResult>> _generate_batch_async(std::unique_ptr<VecVecString> tokens,
+    template <typename Tokens>
+    std::vector<std::future<ctranslate2::GenerationResult>> _generate_batch_async(std::unique_ptr<Tokens> tokens,
                                                                                   size_t max_batch_size,
                                                                                   rust::Str batch_type_str,
-                                                                                  ctranslate2::GenerationOptions &&options) const
+                                                                                  ctranslate2::GenerationOptions &&options,
+                                                                                  const StepCallback *step_callback) const
     {
         if (!tokens || tokens->empty())
             return std::vector<std::future<ctranslate2::GenerationResult>>{};
 
         ctranslate2::BatchType batch_type =
             ctranslate2::str_to_batch_type((std::string)batch_type_str);
-        return _pool->generate_batch_async(
-            tokens->data(), std::move(options), max_batch_size, batch_type);
+        if (!step_callback)
+            return _pool->generate_batch_async(
+                tokens->data(), std::move(options), max_batch_size, batch_type);
+
+        // The examples are batched as in generate_batch_async, but the jobs are posted here to
+        // map the batch_id given to the callback back to the index of the example in the input.
+        auto examples = LoadExamples(tokens->data());
+        auto inputs = std::make_shared<const typename Tokens::DataType>(tokens->release());
+        return _pool->post_examples<ctranslate2::GenerationResult>(
+            examples,
+            max_batch_size,
+            batch_type,
+            [inputs, options = std::move(options), step_callback = *step_callback](
+                ctranslate2::models::SequenceGeneratorReplica &generator, const ctranslate2::Batch &batch)
+            {
+                auto batch_options = options;
+                batch_options.callback = [&step_callback, &batch](ctranslate2::GenerationStepResult result) -> bool
+                {
+                    const size_t input_index = batch.example_index[result.batch_id];
+                    return step_callback.callback(ConvertStepResult(std::move(result), input_index), *step_callback.context);
+                };
+                return generator.generate(BatchInputs(*inputs, batch), batch_options);
+            });
     }
 
-    static ctranslate2::GenerationOptions ConvertGenerationOptions(rust::Box<GenerationOptions> options)
+    // Submits the examples to the replicas and hands each result to Rust from the worker thread
+    // that produced it, in completion order. The contexts are shared by the jobs of the call and
+    // released with the last one, including when the jobs are dropped by the pool without
+    // running, which the Rust side reports as an error. The inputs are only taken from `tokens`
+    // once the call is submitted.
+    template <typename Tokens>
+    void _post_generation(Tokens &tokens,
+                          size_t max_batch_size,
+                          rust::Str batch_type_str,
+                          rust::Box<GenerationOptions> options,
+                          std::shared_ptr<const AsyncStepCallback> step_callback,
+                          AsyncCallbackFunction callback,
+                          rust::Box<GenerateAsyncContext> context) const
     {
-        ctranslate2::GenerationOptions ret;
-        ret.beam_size = options->beam_size;
-        ret.cache_static_prompt = options->cache_static_prompt;
-        ret.disable_unk = options->disable_unk;
-        if (!options->end_token.empty() || !options->empty_end_token_means_stop_on_eos_token)
-            ret.end_token = ConvertVector<rust::Vec<rust::String>, std::vector<std::string>>(options->end_token);
-        ret.include_prompt_in_result = options->include_prompt_in_result;
-        ret.length_penalty = options->length_penalty;
-        ret.max_length = options->max_length;
-        ret.min_alternative_expansion_prob = options->min_alternative_expansion_prob;
-        ret.no_repeat_ngram_size = options->no_repeat_ngram_size;
-        ret.num_hypotheses = options->num_hypotheses;
-        ret.patience = options->patience;
-        ret.repetition_penalty = options->repetition_penalty;
-        ret.return_alternatives = options->return_alternatives;
-        ret.return_end_token = options->return_end_token;
-        ret.return_scores = options->return_scores;
-        ret.sampling_temperature = options->sampling_temperature;
-        ret.sampling_topk = options->sampling_topk;
-        ret.sampling_topp = options->sampling_topp;
+        if (tokens.empty())
+            return;
+
+        auto shared_context = std::make_shared<const rust::Box<GenerateAsyncContext>>(std::move(context));
+        std::shared_ptr<typename Tokens::DataType> inputs;
+        try
+        {
+            ctranslate2::BatchType batch_type =
+                ctranslate2::str_to_batch_type((std::string)batch_type_str);
+            auto generation_options = ConvertGenerationOptions(std::move(options));
+            auto examples = LoadExamples(tokens.data());
+            inputs = std::make_shared<typename Tokens::DataType>(tokens.release());
+            _pool->post_examples<ctranslate2::GenerationResult>(
+                examples,
+                max_batch_size,
+                batch_type,
+                [inputs, options = std::move(generation_options), step_callback, callback, context = shared_context](
+                    ctranslate2::models::SequenceGeneratorReplica &generator, const ctranslate2::Batch &batch)
+                {
+                    auto batch_options = options;
+                    if (step_callback)
+                        batch_options.callback = [&step_callback, &batch](ctranslate2::GenerationStepResult result) -> bool
+                        {
+                            // batch_id is the position of the example in the batch, which the pool
+                            // may have reordered.
+                            const size_t input_index = batch.example_index[result.batch_id];
+                            return step_callback->callback(ConvertStepResult(std::move(result), input_index), *step_callback->context);
+                        };
+
+                    std::vector<ctranslate2::GenerationResult> results;
+                    std::exception_ptr exception;
+                    try
+                    {
+                        results = generator.generate(BatchInputs(*inputs, batch), batch_options);
+                    }
+                    catch (...)
+                    {
+                        exception = std::current_exception();
+                    }
+
+                    for (size_t i = 0; i < batch.example_index.size(); ++i)
+                    {
+                        std::promise<ctranslate2::GenerationResult> promise;
+                        if (exception)
+                            promise.set_exception(exception);
+                        else
+                            promise.set_value(std::move(results[i]));
+                        callback(batch.example_index[i], std::make_unique<GenerationAsyncResult>(promise.get_future()), **context);
+                    }
+                    // The results were moved to Rust, nothing waits on the futures of the pool.
+                    return std::vector<ctranslate2::GenerationResult>(batch.example_index.size());
+                });
+        }
+        catch (...)
+        {
+            // Unless a job was posted, the error is returned to the caller instead, and the
+            // inputs are given back to it.
+            if (!inputs || inputs.use_count() == 1)
+            {
+                if (inputs)
+                    tokens = Tokens(std::move(*inputs));
+                (*shared_context)->discard();
+            }
+            throw;
+        }
+    }
+
+    // Examples from which the pool forms the batches, which only depends on the length of each
+    // sequence. The pool reads token strings, so placeholders are used for token IDs.
+    static std::vector<ctranslate2::Example> LoadExamples(const std::vector<std::vector<std::string>> &tokens)
+    {
+        return ctranslate2::load_examples({tokens});
+    }
+
+    static std::vector<ctranslate2::Example> LoadExamples(const std::vector<std::vector<size_t>> &ids)
+    {
+        std::vector<std::vector<std::string>> placeholders;
+        placeholders.reserve(ids.size());
+        for (const auto &sequence : ids)
+            placeholders.emplace_back(sequence.size());
+        return ctranslate2::load_examples({std::move(placeholders)});
+    }
+
+    // Inputs of the examples of a batch, in the order of the batch.
+    template <typename Sequences>
+    static Sequences BatchInputs(const Sequences &inputs, const ctranslate2::Batch &batch)
+    {
+        Sequences batch_inputs;
+        batch_inputs.reserve(batch.example_index.size());
+        for (const size_t index : batch.example_index)
+            batch_inputs.emplace_back(inputs[index]);
+        return batch_inputs;
+    }
+
+    template <typename Tokens>
+    rust::Vec<ScoringResult> _score_batch(std::unique_ptr<Tokens> tokens,
+                                          size_t max_batch_size,
+                                          rust::Str batch_type_str,
+                                          rust::Box<ScoringOptions> options) const
+    {
+        if (!tokens || tokens->empty())
+            return rust::Vec<ScoringResult>();
+
+        ctranslate2::BatchType batch_type =
+            ctranslate2::str_to_batch_type((std::string)batch_type_str);
+        auto futures = _pool->score_batch_async(
+            tokens->data(), ConvertScoringOptions(std::move(options)), max_batch_size, batch_type);
+        auto results = wait_on_futures(std::move(futures));
+        return ConvertScoringResults(std::move(results));
+    }
+
+    static GenerationStepResult ConvertStepResult(ctranslate2::GenerationStepResult &&result, size_t input_index)
+    {
+        GenerationStepResult converted;
+        converted.batch_id = result.batch_id;
+        converted.input_index = input_index;
+        converted.hypothesis_id = result.hypothesis_id;
+        converted.is_last = result.is_last;
+        converted.log_prob = result.log_prob ? *result.log_prob : 0;
+        converted.log_prob_valid = result.log_prob.has_value();
+        converted.step = result.step;
+        converted.token = rust::String(result.token);
+        converted.token_id = result.token_id;
+        return converted;
+    }
+
+    static ctranslate2::GenerationOptions ConvertGenerationOptions(rust::Box<GenerationOptions> options)
+    {
+        ctranslate2::GenerationOptions ret;
+        ret.beam_size = options->beam_size;
+        ret.cache_static_prompt = options->cache_static_prompt;
+        ret.disable_unk = options->disable_unk;
+        if (!options->end_token.empty() || !options->empty_end_token_means_stop_on_eos_token)
+            ret.end_token = ConvertVector<rust::Vec<rust::String>, std::vector<std::string>>(options->end_token);
+        ret.include_prompt_in_result = options->include_prompt_in_result;
+        ret.length_penalty = options->length_penalty;
+        ret.max_length = options->max_length;
+        ret.min_alternative_expansion_prob = options->min_alternative_expansion_prob;
+        ret.no_repeat_ngram_size = options->no_repeat_ngram_size;
+        ret.num_hypotheses = options->num_hypotheses;
+        ret.patience = options->patience;
+        ret.repetition_penalty = options->repetition_penalty;
+        ret.return_alternatives = options->return_alternatives;
+        ret.return_end_token = options->return_end_token;
+        ret.return_log_prob = options->return_log_prob;
+        ret.return_scores = options->return_scores;
+        ret.sampling_temperature = options->sampling_temperature;
+        ret.sampling_topk = options->sampling_topk;
+        ret.sampling_topp = options->sampling_topp;
         ret.static_prompt = ConvertVector<rust::Vec<rust::String>, std::vector<std::string>>(options->static_prompt);
         ret.suppress_sequences = options->suppress_sequences
                                      ? options->suppress_sequences->data()
@@ -353,10 +1031,7 @@ private:
     {
         rust::Vec<GenerationResult> ret;
         for (auto &result : results)
-            ret.emplace_back(GenerationResult{
-                std::make_unique<VecVecString>(VecVecString(std::move(result.sequences))),
-                std::make_unique<VecVecUsize>(VecVecUsize(std::move(result.sequences_ids))),
-                ConvertVector<std::vector<float>, rust::Vec<float>>(std::move(result.scores))});
+            ret.emplace_back(ConvertGenerationResult(std::move(result)));
         return ret;
     }
 };
@@ -379,3 +1054,384 @@ std::unique_ptr<GeneratorWrapper> new_generator_wrapper(
         intra_threads,
         max_queued_batches);
 }
+
+std::unique_ptr<GeneratorWrapper> new_generator_wrapper_from_files(
+    rust::Vec<ModelFile> files,
+    rust::Str device,
+    rust::Vec<int> device_indicies,
+    rust::Str compute_type,
+    size_t inter_threads,
+    size_t intra_threads,
+    int max_queued_batches)
+{
+    return std::make_unique<GeneratorWrapper>(
+        std::make_shared<RustFilesReader>(std::move(files)),
+        (std::string)device,
+        ConvertVector<rust::Vec<int>, std::vector<int>>(std::move(device_indicies)),
+        (std::string)compute_type,
+        inter_threads,
+        intra_threads,
+        max_queued_batches);
+}
+
+std::unique_ptr<GeneratorWrapper> new_generator_wrapper_from_reader(
+    rust::Box<ModelReaderContext> reader,
+    rust::Str device,
+    rust::Vec<int> device_indicies,
+    rust::Str compute_type,
+    size_t inter_threads,
+    size_t intra_threads,
+    int max_queued_batches)
+{
+    return std::make_unique<GeneratorWrapper>(
+        std::make_shared<RustModelReader>(std::move(reader)),
+        (std::string)device,
+        ConvertVector<rust::Vec<int>, std::vector<int>>(std::move(device_indicies)),
+        (std::string)compute_type,
+        inter_threads,
+        intra_threads,
+        max_queued_batches);
+}
+
+class TranslatorWrapper : public ReplicaPoolHelper<ctranslate2::Translator>
+{
+public:
+    using ReplicaPoolHelper::ReplicaPoolHelper;
+
+    rust::Vec<TranslationResult> translate_batch(std::unique_ptr<VecVecString> source,
+                                                 std::unique_ptr<VecVecString> target_prefix,
+                                                 size_t max_batch_size,
+                                                 rust::Str batch_type_str,
+                                                 rust::Box<TranslationOptions> options) const
+    {
+        if (!source || source->empty())
+            return rust::Vec<TranslationResult>();
+
+        ctranslate2::BatchType batch_type =
+            ctranslate2::str_to_batch_type((std::string)batch_type_str);
+        auto futures = _pool->translate_batch_async(
+            source->data(),
+            target_prefix ? target_prefix->data() : std::vector<std::vector<std::string>>(),
+            ConvertTranslationOptions(std::move(options)),
+            max_batch_size,
+            batch_type);
+        auto results = wait_on_futures(std::move(futures));
+        return ConvertTranslationResults(std::move(results));
+    }
+
+    rust::Vec<ScoringResult> score_batch(std::unique_ptr<VecVecString> source,
+                                         std::unique_ptr<VecVecString> target,
+                                         size_t max_batch_size,
+                                         rust::Str batch_type_str,
+                                         rust::Box<ScoringOptions> options) const
+    {
+        if (!source || source->empty())
+            return rust::Vec<ScoringResult>();
+
+        ctranslate2::BatchType batch_type =
+            ctranslate2::str_to_batch_type((std::string)batch_type_str);
+        auto futures = _pool->score_batch_async(
+            source->data(),
+            target ? target->data() : std::vector<std::vector<std::string>>(),
+            ConvertScoringOptions(std::move(options)),
+            max_batch_size,
+            batch_type);
+        auto results = wait_on_futures(std::move(futures));
+        return ConvertScoringResults(std::move(results));
+    }
+
+private:
+    static ctranslate2::TranslationOptions ConvertTranslationOptions(rust::Box<TranslationOptions> options)
+    {
+        ctranslate2::TranslationOptions ret;
+        ret.beam_size = options->beam_size;
+        ret.coverage_penalty = options->coverage_penalty;
+        ret.disable_unk = options->disable_unk;
+        if (!options->end_token.empty() || !options->empty_end_token_means_stop_on_eos_token)
+            ret.end_token = ConvertVector<rust::Vec<rust::String>, std::vector<std::string>>(options->end_token);
+        ret.length_penalty = options->length_penalty;
+        ret.max_decoding_length = options->max_decoding_length;
+        ret.max_input_length = options->max_input_length;
+        ret.min_alternative_expansion_prob = options->min_alternative_expansion_prob;
+        ret.min_decoding_length = options->min_decoding_length;
+        ret.no_repeat_ngram_size = options->no_repeat_ngram_size;
+        ret.num_hypotheses = options->num_hypotheses;
+        ret.patience = options->patience;
+        ret.prefix_bias_beta = options->prefix_bias_beta;
+        ret.repetition_penalty = options->repetition_penalty;
+        ret.replace_unknowns = options->replace_unknowns;
+        ret.return_alternatives = options->return_alternatives;
+        ret.return_attention = options->return_attention;
+        ret.return_end_token = options->return_end_token;
+        ret.return_scores = options->return_scores;
+        ret.sampling_temperature = options->sampling_temperature;
+        ret.sampling_topk = options->sampling_topk;
+        ret.sampling_topp = options->sampling_topp;
+        ret.suppress_sequences = options->suppress_sequences
+                                     ? options->suppress_sequences->data()
+                                     : std::vector<std::vector<std::string>>();
+        ret.use_vmap = options->use_vmap;
+        return ret;
+    }
+
+    static rust::Vec<TranslationResult> ConvertTranslationResults(std::vector<ctranslate2::TranslationResult> &&results)
+    {
+        rust::Vec<TranslationResult> ret;
+        for (auto &result : results)
+        {
+            rust::Vec<AttentionMatrix> attention;
+            for (auto &matrix : result.attention)
+                attention.emplace_back(AttentionMatrix{
+                    std::make_unique<VecVecF32>(VecVecF32(std::move(matrix)))});
+
+            ret.emplace_back(TranslationResult{
+                std::make_unique<VecVecString>(VecVecString(std::move(result.hypotheses))),
+                ConvertVector<std::vector<float>, rust::Vec<float>>(std::move(result.scores)),
+                std::move(attention)});
+        }
+        return ret;
+    }
+};
+
+std::unique_ptr<TranslatorWrapper> new_translator_wrapper(
+    rust::Str model_path,
+    rust::Str device,
+    rust::Vec<int> device_indicies,
+    rust::Str compute_type,
+    size_t inter_threads,
+    size_t intra_threads,
+    int max_queued_batches)
+{
+    return std::make_unique<TranslatorWrapper>(
+        (std::string)model_path,
+        (std::string)device,
+        ConvertVector<rust::Vec<int>, std::vector<int>>(std::move(device_indicies)),
+        (std::string)compute_type,
+        inter_threads,
+        intra_threads,
+        max_queued_batches);
+}
+
+std::unique_ptr<TranslatorWrapper> new_translator_wrapper_from_reader(
+    rust::Box<ModelReaderContext> reader,
+    rust::Str device,
+    rust::Vec<int> device_indicies,
+    rust::Str compute_type,
+    size_t inter_threads,
+    size_t intra_threads,
+    int max_queued_batches)
+{
+    return std::make_unique<TranslatorWrapper>(
+        std::make_shared<RustModelReader>(std::move(reader)),
+        (std::string)device,
+        ConvertVector<rust::Vec<int>, std::vector<int>>(std::move(device_indicies)),
+        (std::string)compute_type,
+        inter_threads,
+        intra_threads,
+        max_queued_batches);
+}
+
+class EncoderWrapper : public ReplicaPoolHelper<ctranslate2::Encoder>
+{
+public:
+    using ReplicaPoolHelper::ReplicaPoolHelper;
+
+    EncoderForwardOutput forward_batch(std::unique_ptr<VecVecString> tokens,
+                                       std::unique_ptr<VecVecUsize> token_type_ids) const
+    {
+        if (!tokens || tokens->empty())
+            throw std::invalid_argument("forward_batch requires at least one input");
+
+        auto future = _pool->forward_batch_async(
+            tokens->data(),
+            token_type_ids ? token_type_ids->data() : std::vector<std::vector<size_t>>());
+        auto output = future.get();
+        return EncoderForwardOutput{
+            std::make_unique<StorageViewWrapper>(std::move(output.last_hidden_state)),
+            output.pooler_output
+                ? std::make_unique<StorageViewWrapper>(std::move(*output.pooler_output))
+                : std::unique_ptr<StorageViewWrapper>()};
+    }
+};
+
+std::unique_ptr<EncoderWrapper> new_encoder_wrapper(
+    rust::Str model_path,
+    rust::Str device,
+    rust::Vec<int> device_indicies,
+    rust::Str compute_type,
+    size_t inter_threads,
+    size_t intra_threads,
+    int max_queued_batches)
+{
+    return std::make_unique<EncoderWrapper>(
+        (std::string)model_path,
+        (std::string)device,
+        ConvertVector<rust::Vec<int>, std::vector<int>>(std::move(device_indicies)),
+        (std::string)compute_type,
+        inter_threads,
+        intra_threads,
+        max_queued_batches);
+}
+
+std::unique_ptr<EncoderWrapper> new_encoder_wrapper_from_reader(
+    rust::Box<ModelReaderContext> reader,
+    rust::Str device,
+    rust::Vec<int> device_indicies,
+    rust::Str compute_type,
+    size_t inter_threads,
+    size_t intra_threads,
+    int max_queued_batches)
+{
+    return std::make_unique<EncoderWrapper>(
+        std::make_shared<RustModelReader>(std::move(reader)),
+        (std::string)device,
+        ConvertVector<rust::Vec<int>, std::vector<int>>(std::move(device_indicies)),
+        (std::string)compute_type,
+        inter_threads,
+        intra_threads,
+        max_queued_batches);
+}
+
+class WhisperWrapper : public ReplicaPoolHelper<ctranslate2::models::Whisper>
+{
+public:
+    using ReplicaPoolHelper::ReplicaPoolHelper;
+
+    bool is_multilingual() const
+    {
+        return _pool->is_multilingual();
+    }
+
+    rust::Vec<WhisperGenerationResult> generate(const StorageViewWrapper &features,
+                                                std::unique_ptr<VecVecString> prompts,
+                                                rust::Box<WhisperOptions> options) const
+    {
+        if (!prompts || prompts->empty())
+            return rust::Vec<WhisperGenerationResult>();
+
+        auto futures = _pool->generate(features.view(), prompts->data(), ConvertWhisperOptions(std::move(options)));
+        auto results = wait_on_futures(std::move(futures));
+        return ConvertWhisperGenerationResults(std::move(results));
+    }
+
+    rust::Vec<WhisperLanguageDetection> detect_language(const StorageViewWrapper &features) const
+    {
+        auto futures = _pool->detect_language(features.view());
+        auto results = wait_on_futures(std::move(futures));
+
+        rust::Vec<WhisperLanguageDetection> ret;
+        for (auto &result : results)
+        {
+            WhisperLanguageDetection detection;
+            for (auto &language : result)
+            {
+                detection.languages.emplace_back(std::move(language.first));
+                detection.probabilities.push_back(language.second);
+            }
+            ret.emplace_back(std::move(detection));
+        }
+        return ret;
+    }
+
+    rust::Vec<WhisperAlignmentResult> align(const StorageViewWrapper &features,
+                                            rust::Slice<const size_t> start_sequence,
+                                            std::unique_ptr<VecVecUsize> text_tokens,
+                                            size_t num_frames,
+                                            size_t median_filter_width) const
+    {
+        if (!text_tokens || text_tokens->empty())
+            return rust::Vec<WhisperAlignmentResult>();
+
+        auto futures = _pool->align(features.view(),
+                                    std::vector<size_t>(start_sequence.begin(), start_sequence.end()),
+                                    text_tokens->data(),
+                                    (ctranslate2::dim_t)num_frames,
+                                    (ctranslate2::dim_t)median_filter_width);
+        auto results = wait_on_futures(std::move(futures));
+
+        rust::Vec<WhisperAlignmentResult> ret;
+        for (auto &result : results)
+        {
+            WhisperAlignmentResult alignment;
+            for (const auto &pair : result.alignments)
+            {
+                alignment.text_indices.push_back((size_t)pair.first);
+                alignment.time_indices.push_back((size_t)pair.second);
+            }
+            alignment.text_token_probs = ConvertVector<std::vector<float>, rust::Vec<float>>(std::move(result.text_token_probs));
+            ret.emplace_back(std::move(alignment));
+        }
+        return ret;
+    }
+
+private:
+    static ctranslate2::models::WhisperOptions ConvertWhisperOptions(rust::Box<WhisperOptions> options)
+    {
+        ctranslate2::models::WhisperOptions ret;
+        ret.beam_size = options->beam_size;
+        ret.length_penalty = options->length_penalty;
+        ret.max_initial_timestamp_index = options->max_initial_timestamp_index;
+        ret.max_length = options->max_length;
+        ret.no_repeat_ngram_size = options->no_repeat_ngram_size;
+        ret.num_hypotheses = options->num_hypotheses;
+        ret.patience = options->patience;
+        ret.repetition_penalty = options->repetition_penalty;
+        ret.return_no_speech_prob = options->return_no_speech_prob;
+        ret.return_scores = options->return_scores;
+        ret.sampling_temperature = options->sampling_temperature;
+        ret.sampling_topk = options->sampling_topk;
+        ret.suppress_blank = options->suppress_blank;
+        ret.suppress_tokens = ConvertVector<rust::Vec<int>, std::vector<int>>(options->suppress_tokens);
+        return ret;
+    }
+
+    static rust::Vec<WhisperGenerationResult> ConvertWhisperGenerationResults(std::vector<ctranslate2::models::WhisperGenerationResult> &&results)
+    {
+        rust::Vec<WhisperGenerationResult> ret;
+        for (auto &result : results)
+            ret.emplace_back(WhisperGenerationResult{
+                std::make_unique<VecVecString>(VecVecString(std::move(result.sequences))),
+                std::make_unique<VecVecUsize>(VecVecUsize(std::move(result.sequences_ids))),
+                ConvertVector<std::vector<float>, rust::Vec<float>>(std::move(result.scores)),
+                result.no_speech_prob});
+        return ret;
+    }
+};
+
+std::unique_ptr<WhisperWrapper> new_whisper_wrapper(
+    rust::Str model_path,
+    rust::Str device,
+    rust::Vec<int> device_indicies,
+    rust::Str compute_type,
+    size_t inter_threads,
+    size_t intra_threads,
+    int max_queued_batches)
+{
+    return std::make_unique<WhisperWrapper>(
+        (std::string)model_path,
+        (std::string)device,
+        ConvertVector<rust::Vec<int>, std::vector<int>>(std::move(device_indicies)),
+        (std::string)compute_type,
+        inter_threads,
+        intra_threads,
+        max_queued_batches);
+}
+
+std::unique_ptr<WhisperWrapper> new_whisper_wrapper_from_reader(
+    rust::Box<ModelReaderContext> reader,
+    rust::Str device,
+    rust::Vec<int> device_indicies,
+    rust::Str compute_type,
+    size_t inter_threads,
+    size_t intra_threads,
+    int max_queued_batches)
+{
+    return std::make_unique<WhisperWrapper>(
+        std::make_shared<RustModelReader>(std::move(reader)),
+        (std::string)device,
+        ConvertVector<rust::Vec<int>, std::vector<int>>(std::move(device_indicies)),
+        (std::string)compute_type,
+        inter_threads,
+        intra_threads,
+        max_queued_batches);
+}
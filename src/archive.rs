@@ -0,0 +1,526 @@
+//! Models packed into a single `.tar`, `.tar.zst` or `.zip` file.
+
+use crate::ModelReader;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+#[cfg(any(feature = "tar", feature = "zip"))]
+use std::io::{Seek, SeekFrom};
+use std::path::Path;
+#[cfg(all(feature = "tar", feature = "zstd"))]
+use std::sync::Mutex;
+
+enum Archive {
+    // Offset and size of each file in a `.tar` or `.zip` archive, which is read in place.
+    #[cfg(any(feature = "tar", feature = "zip"))]
+    Stored(HashMap<String, (u64, u64)>),
+    // Index of each file among the entries of the archive. Compressed archives cannot be read
+    // in place, so the files are decompressed from a stream which is kept between two files.
+    #[cfg(all(feature = "tar", feature = "zstd"))]
+    TarZst(HashMap<String, usize>, Mutex<Option<TarZstStream>>),
+}
+
+/// Reads a model from an archive of its directory, without extracting it to disk.
+///
+/// The model files are looked up by file name, so the archive can contain them at its root or
+/// in a single subdirectory; archives with two files of the same name are rejected. Models are
+/// loaded from archives by passing the archive path to `Generator::new` and the other
+/// constructors, or with `from_reader`.
+///
+/// The files are streamed from the archive while the model loads. The files of a `.zip`
+/// archive must be stored without compression, as `pack_model` does. A `.tar.zst` archive is
+/// decompressed once when opened to list its files, and then once more as the files are read,
+/// in their order in the archive; reading a file stored before the previous one restarts the
+/// decompression.
+pub struct ArchiveModelReader {
+    path: String,
+    archive: Archive,
+}
+
+impl ArchiveModelReader {
+    /// Returns whether `path` is a file with an archive extension supported by the enabled
+    /// features.
+    pub fn is_archive(path: &str) -> bool {
+        ArchiveFormat::from_path(path).is_some() && Path::new(path).is_file()
+    }
+
+    pub fn open(path: &str) -> io::Result<ArchiveModelReader> {
+        let archive = match ArchiveFormat::from_path(path) {
+            #[cfg(feature = "tar")]
+            Some(ArchiveFormat::Tar) => {
+                Archive::Stored(index_tar(File::open(path)?, |_, entry| {
+                    (entry.raw_file_position(), entry.size())
+                })?)
+            }
+            #[cfg(all(feature = "tar", feature = "zstd"))]
+            Some(ArchiveFormat::TarZst) => Archive::TarZst(
+                index_tar(zstd::Decoder::new(File::open(path)?)?, |index, _| index)?,
+                Mutex::new(None),
+            ),
+            #[cfg(feature = "zip")]
+            Some(ArchiveFormat::Zip) => Archive::Stored(index_zip(File::open(path)?)?),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{path} is not a supported model archive"),
+                ))
+            }
+        };
+        Ok(ArchiveModelReader {
+            path: path.to_string(),
+            archive,
+        })
+    }
+}
+
+impl ModelReader for ArchiveModelReader {
+    fn model_id(&self) -> String {
+        self.path.clone()
+    }
+
+    fn get_file(&self, filename: &str) -> io::Result<Option<Box<dyn Read + '_>>> {
+        match &self.archive {
+            #[cfg(any(feature = "tar", feature = "zip"))]
+            Archive::Stored(files) => {
+                let Some(&(offset, size)) = files.get(filename) else {
+                    return Ok(None);
+                };
+                let mut file = File::open(&self.path)?;
+                file.seek(SeekFrom::Start(offset))?;
+                Ok(Some(Box::new(file.take(size))))
+            }
+            #[cfg(all(feature = "tar", feature = "zstd"))]
+            Archive::TarZst(files, last_stream) => {
+                let Some(&index) = files.get(filename) else {
+                    return Ok(None);
+                };
+                // Continue from the previous file when it comes first, otherwise restart.
+                let stream = last_stream.lock().unwrap().take();
+                let mut stream = match stream.filter(|stream| stream.next <= index) {
+                    Some(stream) => stream,
+                    None => TarZstStream::open(&self.path)?,
+                };
+                let size = stream.seek_entry(index)?;
+                Ok(Some(Box::new(TarZstEntry {
+                    last_stream,
+                    stream: Some(stream),
+                    size,
+                })))
+            }
+        }
+    }
+}
+
+// Decompressed content of a `.tar.zst` archive, positioned before the header of the entry
+// `next` once `skip` bytes are read.
+#[cfg(all(feature = "tar", feature = "zstd"))]
+struct TarZstStream {
+    decoder: zstd::Decoder<'static, io::BufReader<File>>,
+    next: usize,
+    skip: u64,
+}
+
+#[cfg(all(feature = "tar", feature = "zstd"))]
+impl TarZstStream {
+    fn open(path: &str) -> io::Result<TarZstStream> {
+        Ok(TarZstStream {
+            decoder: zstd::Decoder::new(File::open(path)?)?,
+            next: 0,
+            skip: 0,
+        })
+    }
+
+    // Moves to the content of the entry `index`, which must not come before `next`, and
+    // returns its size.
+    fn seek_entry(&mut self, index: usize) -> io::Result<u64> {
+        let skipped = io::copy(&mut (&mut self.decoder).take(self.skip), &mut io::sink())?;
+        if skipped < self.skip {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.skip = 0;
+        // The tar reader does not read ahead: once it returns an entry, the decoder is
+        // positioned at the start of its content.
+        let mut archive = tar::Archive::new(&mut self.decoder);
+        for entry in archive.entries()? {
+            let entry = entry?;
+            self.next += 1;
+            if self.next > index {
+                let size = entry.size();
+                // The content is padded to the tar block size.
+                self.skip = size.div_ceil(512) * 512;
+                return Ok(size);
+            }
+        }
+        Err(io::ErrorKind::UnexpectedEof.into())
+    }
+}
+
+// A file of a `.tar.zst` archive, which gives the stream back once read.
+#[cfg(all(feature = "tar", feature = "zstd"))]
+struct TarZstEntry<'a> {
+    last_stream: &'a Mutex<Option<TarZstStream>>,
+    stream: Option<TarZstStream>,
+    size: u64,
+}
+
+#[cfg(all(feature = "tar", feature = "zstd"))]
+impl Read for TarZstEntry<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let stream = self
+            .stream
+            .as_mut()
+            .expect("the stream is only taken on drop");
+        let len = (buf.len() as u64).min(self.size) as usize;
+        // The decoder fails on empty buffers.
+        if len == 0 {
+            return Ok(0);
+        }
+        let read = stream.decoder.read(&mut buf[..len])?;
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.size -= read as u64;
+        stream.skip -= read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(all(feature = "tar", feature = "zstd"))]
+impl Drop for TarZstEntry<'_> {
+    fn drop(&mut self) {
+        *self.last_stream.lock().unwrap() = self.stream.take();
+    }
+}
+
+/// Packs the files of a model directory into an archive whose format is given by the
+/// extension of `archive_path`: `.tar`, `.tar.zst` (or `.tzst`) or `.zip`.
+pub fn pack_model(model_path: &str, archive_path: &str) -> io::Result<()> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(model_path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            files.push((
+                entry.file_name().to_string_lossy().into_owned(),
+                entry.path(),
+            ));
+        }
+    }
+    files.sort();
+
+    let format = ArchiveFormat::from_path(archive_path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{archive_path} does not have a supported archive extension"),
+        )
+    })?;
+    let output = File::create(archive_path)?;
+    match format {
+        #[cfg(feature = "tar")]
+        ArchiveFormat::Tar => write_tar(output, &files).map(drop),
+        #[cfg(all(feature = "tar", feature = "zstd"))]
+        ArchiveFormat::TarZst => {
+            let encoder = zstd::Encoder::new(output, 0)?;
+            write_tar(encoder, &files)?.finish().map(drop)
+        }
+        #[cfg(feature = "zip")]
+        ArchiveFormat::Zip => {
+            let mut writer = zip::ZipWriter::new(output);
+            for (name, path) in &files {
+                let mut file = File::open(path)?;
+                // Weights barely compress, store them as is so that they are fast to read.
+                let options = zip::write::FileOptions::default()
+                    .compression_method(zip::CompressionMethod::Stored)
+                    .large_file(file.metadata()?.len() >= u32::MAX as u64);
+                writer.start_file(name.as_str(), options)?;
+                io::copy(&mut file, &mut writer)?;
+            }
+            writer.finish()?;
+            Ok(())
+        }
+    }
+}
+
+enum ArchiveFormat {
+    #[cfg(feature = "tar")]
+    Tar,
+    #[cfg(all(feature = "tar", feature = "zstd"))]
+    TarZst,
+    #[cfg(feature = "zip")]
+    Zip,
+}
+
+impl ArchiveFormat {
+    fn from_path(path: &str) -> Option<ArchiveFormat> {
+        let path = path.to_lowercase();
+        #[cfg(all(feature = "tar", feature = "zstd"))]
+        if path.ends_with(".tar.zst") || path.ends_with(".tzst") {
+            return Some(ArchiveFormat::TarZst);
+        }
+        #[cfg(feature = "tar")]
+        if path.ends_with(".tar") {
+            return Some(ArchiveFormat::Tar);
+        }
+        #[cfg(feature = "zip")]
+        if path.ends_with(".zip") {
+            return Some(ArchiveFormat::Zip);
+        }
+        None
+    }
+}
+
+fn file_name(path: &str) -> Option<String> {
+    Some(Path::new(path).file_name()?.to_string_lossy().into_owned())
+}
+
+// Adds a file to the index of an archive, which must not already have a file of that name.
+#[cfg(any(feature = "tar", feature = "zip"))]
+fn insert_file<T>(files: &mut HashMap<String, T>, name: String, value: T) -> io::Result<()> {
+    if files.contains_key(&name) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("the archive has several files named {name}"),
+        ));
+    }
+    files.insert(name, value);
+    Ok(())
+}
+
+// Indexes the files of a tar archive by file name, with the value returned by `value` for
+// their index among the entries and their entry.
+#[cfg(feature = "tar")]
+fn index_tar<R: Read, T>(
+    reader: R,
+    mut value: impl FnMut(usize, &tar::Entry<'_, R>) -> T,
+) -> io::Result<HashMap<String, T>> {
+    let mut archive = tar::Archive::new(reader);
+    let mut files = HashMap::new();
+    for (index, entry) in archive.entries()?.enumerate() {
+        let entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        if let Some(name) = file_name(&entry.path()?.to_string_lossy()) {
+            insert_file(&mut files, name, value(index, &entry))?;
+        }
+    }
+    Ok(files)
+}
+
+// Indexes the files of a zip archive by file name, with the offset and size of their content.
+#[cfg(feature = "zip")]
+fn index_zip(file: File) -> io::Result<HashMap<String, (u64, u64)>> {
+    let mut archive = zip::ZipArchive::new(file)?;
+    let mut files = HashMap::new();
+    for index in 0..archive.len() {
+        let entry = archive.by_index(index)?;
+        if entry.is_dir() {
+            continue;
+        }
+        if entry.compression() != zip::CompressionMethod::Stored {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} is compressed, only stored files are supported",
+                    entry.name()
+                ),
+            ));
+        }
+        if let Some(name) = file_name(entry.name()) {
+            insert_file(&mut files, name, (entry.data_start(), entry.size()))?;
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(feature = "tar")]
+fn write_tar<W: io::Write>(writer: W, files: &[(String, std::path::PathBuf)]) -> io::Result<W> {
+    let mut builder = tar::Builder::new(writer);
+    for (name, path) in files {
+        builder.append_path_with_name(path, name)?;
+    }
+    builder.into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn model_files() -> Vec<(&'static str, Vec<u8>)> {
+        vec![
+            ("config.json", b"{\"layers\": 2}".to_vec()),
+            // Not a multiple of the tar block size, so that the padding is skipped.
+            ("model.bin", (0..3000).map(|i| (i % 251) as u8).collect()),
+            ("vocabulary.txt", b"<s>\n</s>\nhello\n".to_vec()),
+        ]
+    }
+
+    fn read_file(reader: &ArchiveModelReader, filename: &str) -> Option<Vec<u8>> {
+        let mut file = reader.get_file(filename).unwrap()?;
+        let mut content = Vec::new();
+        file.read_to_end(&mut content).unwrap();
+        Some(content)
+    }
+
+    // Reads the files out of their order in the archive, and one of them twice.
+    fn check_model(reader: &ArchiveModelReader) {
+        let files: HashMap<_, _> = model_files().into_iter().collect();
+        for filename in ["model.bin", "config.json", "vocabulary.txt", "model.bin"] {
+            assert_eq!(read_file(reader, filename).as_ref(), Some(&files[filename]));
+        }
+        assert!(read_file(reader, "vocabulary.json").is_none());
+    }
+
+    #[cfg(feature = "tar")]
+    #[test]
+    fn tar_files_are_found_in_a_subdirectory() {
+        let dir = TempDir::new("tar-prefix");
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_size(0);
+        builder
+            .append_data(&mut header, "model/", io::empty())
+            .unwrap();
+        for (name, content) in model_files() {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            builder
+                .append_data(&mut header, format!("model/{name}"), content.as_slice())
+                .unwrap();
+        }
+        let path = dir.join("model.tar");
+        std::fs::write(&path, builder.into_inner().unwrap()).unwrap();
+
+        let path = path.to_str().unwrap();
+        assert!(ArchiveModelReader::is_archive(path));
+        check_model(&ArchiveModelReader::open(path).unwrap());
+    }
+
+    #[cfg(feature = "zip")]
+    fn write_zip(path: &Path, files: &[(String, Vec<u8>)], method: zip::CompressionMethod) {
+        let mut writer = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = zip::write::FileOptions::default().compression_method(method);
+        for (name, content) in files {
+            if let Some((directory, _)) = name.rsplit_once('/') {
+                // Directories may be listed more than once.
+                let _ = writer.add_directory(format!("{directory}/"), options);
+            }
+            writer.start_file(name.as_str(), options).unwrap();
+            io::Write::write_all(&mut writer, content).unwrap();
+        }
+        std::fs::write(path, writer.finish().unwrap().into_inner()).unwrap();
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn zip_files_are_found_in_a_subdirectory() {
+        let dir = TempDir::new("zip-prefix");
+        let files: Vec<_> = model_files()
+            .into_iter()
+            .map(|(name, content)| (format!("model/{name}"), content))
+            .collect();
+        let path = dir.join("model.zip");
+        write_zip(&path, &files, zip::CompressionMethod::Stored);
+
+        let path = path.to_str().unwrap();
+        assert!(ArchiveModelReader::is_archive(path));
+        check_model(&ArchiveModelReader::open(path).unwrap());
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn compressed_zip_files_are_rejected() {
+        let dir = TempDir::new("zip-deflated");
+        let files: Vec<_> = model_files()
+            .into_iter()
+            .map(|(name, content)| (name.to_string(), content))
+            .collect();
+        let path = dir.join("model.zip");
+        write_zip(&path, &files, zip::CompressionMethod::Deflated);
+
+        let error = ArchiveModelReader::open(path.to_str().unwrap())
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn duplicate_file_names_are_rejected() {
+        let dir = TempDir::new("duplicates");
+        let files = [
+            ("a/config.json".to_string(), b"{}".to_vec()),
+            ("b/config.json".to_string(), b"{}".to_vec()),
+        ];
+        let mut paths = Vec::new();
+        #[cfg(feature = "tar")]
+        {
+            let mut builder = tar::Builder::new(Vec::new());
+            for (name, content) in &files {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(content.len() as u64);
+                builder
+                    .append_data(&mut header, name, content.as_slice())
+                    .unwrap();
+            }
+            let path = dir.join("model.tar");
+            std::fs::write(&path, builder.into_inner().unwrap()).unwrap();
+            paths.push(path);
+        }
+        #[cfg(feature = "zip")]
+        {
+            let path = dir.join("model.zip");
+            write_zip(&path, &files, zip::CompressionMethod::Stored);
+            paths.push(path);
+        }
+        for path in paths {
+            let error = ArchiveModelReader::open(path.to_str().unwrap())
+                .err()
+                .unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn pack_model_roundtrip() {
+        let dir = TempDir::new("pack");
+        let model_path = dir.join("model");
+        std::fs::create_dir(&model_path).unwrap();
+        for (name, content) in model_files() {
+            std::fs::write(model_path.join(name), content).unwrap();
+        }
+        let extensions = [
+            #[cfg(feature = "tar")]
+            "tar",
+            #[cfg(all(feature = "tar", feature = "zstd"))]
+            "tar.zst",
+            #[cfg(feature = "zip")]
+            "zip",
+        ];
+        for extension in extensions {
+            let path = dir.join(format!("model.{extension}"));
+            let path = path.to_str().unwrap();
+            pack_model(model_path.to_str().unwrap(), path).unwrap();
+            assert!(ArchiveModelReader::is_archive(path));
+            check_model(&ArchiveModelReader::open(path).unwrap());
+        }
+    }
+
+    #[test]
+    fn is_archive_needs_a_supported_file() {
+        let dir = TempDir::new("is-archive");
+        let model_path = dir.join("model.bin");
+        std::fs::write(&model_path, b"weights").unwrap();
+        assert!(!ArchiveModelReader::is_archive(
+            model_path.to_str().unwrap()
+        ));
+        // A directory named like an archive, or a missing archive.
+        let directory = dir.join("model.tar");
+        std::fs::create_dir(&directory).unwrap();
+        assert!(!ArchiveModelReader::is_archive(directory.to_str().unwrap()));
+        assert!(!ArchiveModelReader::is_archive(
+            dir.join("missing.zip").to_str().unwrap()
+        ));
+        assert!(ArchiveModelReader::open(model_path.to_str().unwrap()).is_err());
+    }
+}
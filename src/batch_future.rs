@@ -0,0 +1,334 @@
+use crate::callback::{CallbackPanic, TokenLogProbs};
+use crate::{ffi, CTranslate2Error, CancellationToken, ErrorContext, GenerationResult};
+use cxx::UniquePtr;
+use futures_core::Stream;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+pub(crate) struct GenerateBatchState {
+    pub(crate) results: Vec<Option<Result<GenerationResult, CTranslate2Error>>>,
+    // Indices of the results in completion order, for `GenerateBatchStream`.
+    pub(crate) ready: VecDeque<usize>,
+    pub(crate) remaining: usize,
+    pub(crate) waker: Option<Waker>,
+    // Whether each result was completed, which only happens once.
+    completed: Vec<bool>,
+}
+
+impl GenerateBatchState {
+    pub(crate) fn new(size: usize) -> Arc<Mutex<GenerateBatchState>> {
+        Arc::new(Mutex::new(GenerateBatchState {
+            results: (0..size).map(|_| None).collect(),
+            ready: VecDeque::new(),
+            remaining: size,
+            waker: None,
+            completed: vec![false; size],
+        }))
+    }
+
+    pub(crate) fn complete(
+        state: &Mutex<GenerateBatchState>,
+        index: usize,
+        result: Result<GenerationResult, CTranslate2Error>,
+    ) {
+        let mut state = state.lock().unwrap();
+        if state.completed[index] {
+            return;
+        }
+        state.completed[index] = true;
+        state.results[index] = Some(result);
+        state.ready.push_back(index);
+        state.remaining -= 1;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+// A state and the position of a result in it.
+pub(crate) type ResultTarget = (Arc<Mutex<GenerateBatchState>>, usize);
+
+// Where the results of a job go.
+pub(crate) enum ResultTargets {
+    // The results of the whole call, in order.
+    Batch(Arc<Mutex<GenerateBatchState>>),
+    // One target per example of the job, when it only runs part of a call or serves
+    // several callers.
+    Examples(Vec<ResultTarget>),
+}
+
+// Receives the targets of a context that was discarded, so that the caller can complete them.
+pub(crate) type ReturnedTargets = Arc<Mutex<Option<ResultTargets>>>;
+
+// Owned by the jobs of an asynchronous call, and released with the last of them.
+pub struct GenerateAsyncContext {
+    targets: ResultTargets,
+    pub(crate) context: ErrorContext,
+    pub(crate) log_probs: Option<TokenLogProbs>,
+    // Panic of the step callback of the call, if it has one.
+    pub(crate) step_panic: Option<CallbackPanic>,
+    pub(crate) cancellation: Option<CancellationToken>,
+    // Set by the step callback when the cancellation token stopped the decoding.
+    pub(crate) stopped: Arc<AtomicBool>,
+    // Whether the result of each example of the call was delivered.
+    delivered: Mutex<Vec<bool>>,
+    discarded: AtomicBool,
+    returned: Option<ReturnedTargets>,
+}
+
+impl GenerateAsyncContext {
+    pub(crate) fn new(targets: ResultTargets, context: ErrorContext) -> GenerateAsyncContext {
+        let size = match &targets {
+            ResultTargets::Batch(state) => state.lock().unwrap().results.len(),
+            ResultTargets::Examples(targets) => targets.len(),
+        };
+        GenerateAsyncContext {
+            targets,
+            context,
+            log_probs: None,
+            step_panic: None,
+            cancellation: None,
+            stopped: Arc::default(),
+            delivered: Mutex::new(vec![false; size]),
+            discarded: AtomicBool::new(false),
+            returned: None,
+        }
+    }
+
+    // Gives the targets back through `returned` if the call could not be submitted.
+    pub(crate) fn returning_targets(mut self, returned: ReturnedTargets) -> GenerateAsyncContext {
+        self.returned = Some(returned);
+        self
+    }
+
+    // Called by C++ when the call could not be submitted. The error is returned to the caller,
+    // so the results are not completed when the context is dropped.
+    pub(crate) fn discard(&self) {
+        self.discarded.store(true, Ordering::SeqCst);
+    }
+
+    fn complete(&self, index: usize, result: Result<GenerationResult, CTranslate2Error>) {
+        match &self.targets {
+            ResultTargets::Batch(state) => GenerateBatchState::complete(state, index, result),
+            ResultTargets::Examples(targets) => {
+                let (state, index) = &targets[index];
+                GenerateBatchState::complete(state, *index, result)
+            }
+        }
+    }
+
+    // Called from the CTranslate2 thread that generated the example at `index`.
+    pub(crate) fn on_ready(
+        index: usize,
+        mut result: UniquePtr<ffi::GenerationAsyncResult>,
+        context: &GenerateAsyncContext,
+    ) {
+        let result = result
+            .pin_mut()
+            .result()
+            .map_err(|ex| CTranslate2Error::from_exception(context.context.clone(), ex))
+            .map(|result| GenerationResult::from_ffi(result, index, context.log_probs.as_ref()));
+        let result = context.stopped_result(result);
+        context.delivered.lock().unwrap()[index] = true;
+        context.complete(index, result);
+    }
+}
+
+impl GenerateAsyncContext {
+    // Replaces the result of an example once the step callback stopped the call, since the
+    // hypotheses are then incomplete.
+    fn stopped_result(
+        &self,
+        result: Result<GenerationResult, CTranslate2Error>,
+    ) -> Result<GenerationResult, CTranslate2Error> {
+        // Once the step callback panicked, it stops the decoding of every example of the call.
+        let panic = self.step_panic.as_ref();
+        if let Some(message) = panic.and_then(|panic| panic.lock().unwrap().clone()) {
+            return Err(CTranslate2Error::CallbackPanic {
+                context: self.context.clone(),
+                message,
+            });
+        }
+        // The token only stops the decoding once it is cancelled or expired, which it stays.
+        match &self.cancellation {
+            Some(cancellation) if self.stopped.load(Ordering::SeqCst) => {
+                cancellation.check(self.context.clone()).and(result)
+            }
+            _ => result,
+        }
+    }
+}
+
+impl Drop for GenerateAsyncContext {
+    // The jobs are dropped without running when the model is dropped first.
+    fn drop(&mut self) {
+        if self.discarded.load(Ordering::SeqCst) {
+            if let Some(returned) = &self.returned {
+                let targets =
+                    std::mem::replace(&mut self.targets, ResultTargets::Examples(Vec::new()));
+                *returned.lock().unwrap() = Some(targets);
+            }
+            return;
+        }
+        let delivered = std::mem::take(self.delivered.get_mut().unwrap());
+        for (index, delivered) in delivered.into_iter().enumerate() {
+            if !delivered {
+                let context = self.context.clone();
+                self.complete(index, Err(CTranslate2Error::Dropped { context }));
+            }
+        }
+    }
+}
+
+/// Future returned by `Generator::generate_batch_async` and `generate_requests_async`. It
+/// completes once every example of the batch has been generated, without blocking any thread
+/// of the async runtime.
+pub struct GenerateBatchFuture {
+    pub(crate) state: Arc<Mutex<GenerateBatchState>>,
+    pub(crate) error: Option<CTranslate2Error>,
+}
+
+impl Future for GenerateBatchFuture {
+    type Output = Result<Vec<GenerationResult>, CTranslate2Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if let Some(error) = this.error.take() {
+            return Poll::Ready(Err(error));
+        }
+        let mut state = this.state.lock().unwrap();
+        if state.remaining > 0 {
+            state.waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        Poll::Ready(
+            state
+                .results
+                .drain(..)
+                .map(|result| result.expect("missing generation result"))
+                .collect(),
+        )
+    }
+}
+
+/// Stream returned by `Generator::generate_batch_unordered`. It yields the index of each
+/// example with its result as soon as the example is done, so short prompts are not held back
+/// by the slowest one of the batch.
+pub struct GenerateBatchStream {
+    pub(crate) state: Arc<Mutex<GenerateBatchState>>,
+}
+
+impl Stream for GenerateBatchStream {
+    type Item = (usize, Result<GenerationResult, CTranslate2Error>);
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(index) = state.ready.pop_front() {
+            let result = state.results[index]
+                .take()
+                .expect("missing generation result");
+            return Poll::Ready(Some((index, result)));
+        }
+        if state.remaining == 0 {
+            return Poll::Ready(None);
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::context;
+
+    #[test]
+    fn dropped_context_completes_the_pending_results() {
+        let state = GenerateBatchState::new(2);
+        let async_context =
+            GenerateAsyncContext::new(ResultTargets::Batch(state.clone()), context());
+        async_context.delivered.lock().unwrap()[0] = true;
+        drop(async_context);
+
+        let state = state.lock().unwrap();
+        assert_eq!(state.remaining, 1);
+        assert!(state.results[0].is_none());
+        assert!(matches!(
+            state.results[1],
+            Some(Err(CTranslate2Error::Dropped { .. }))
+        ));
+    }
+
+    #[test]
+    fn discarded_context_completes_nothing() {
+        let state = GenerateBatchState::new(1);
+        let async_context =
+            GenerateAsyncContext::new(ResultTargets::Examples(vec![(state.clone(), 0)]), context());
+        async_context.discard();
+        drop(async_context);
+
+        assert_eq!(state.lock().unwrap().remaining, 1);
+    }
+
+    #[test]
+    fn discarded_context_returns_its_targets() {
+        let state = GenerateBatchState::new(1);
+        let returned = ReturnedTargets::default();
+        let async_context =
+            GenerateAsyncContext::new(ResultTargets::Examples(vec![(state.clone(), 0)]), context())
+                .returning_targets(returned.clone());
+        async_context.discard();
+        drop(async_context);
+
+        let targets = returned.lock().unwrap().take();
+        assert!(matches!(targets, Some(ResultTargets::Examples(targets)) if targets.len() == 1));
+        assert_eq!(state.lock().unwrap().remaining, 1);
+    }
+
+    #[test]
+    fn results_fail_once_the_token_stopped_the_decoding() {
+        let state = GenerateBatchState::new(1);
+        let mut async_context =
+            GenerateAsyncContext::new(ResultTargets::Batch(state.clone()), context());
+        let cancellation = CancellationToken::new();
+        async_context.cancellation = Some(cancellation.clone());
+        cancellation.cancel();
+        // Results completed before the token stopped the decoding are kept.
+        assert!(async_context
+            .stopped_result(Ok(GenerationResult::default()))
+            .is_ok());
+
+        async_context.stopped.store(true, Ordering::SeqCst);
+        assert!(matches!(
+            async_context.stopped_result(Ok(GenerationResult::default())),
+            Err(CTranslate2Error::Cancelled { .. })
+        ));
+    }
+
+    #[test]
+    fn results_are_completed_once() {
+        let state = GenerateBatchState::new(1);
+        GenerateBatchState::complete(
+            &state,
+            0,
+            Err(CTranslate2Error::Dropped { context: context() }),
+        );
+        GenerateBatchState::complete(
+            &state,
+            0,
+            Err(CTranslate2Error::Cancelled { context: context() }),
+        );
+
+        let state = state.lock().unwrap();
+        assert_eq!(state.remaining, 0);
+        assert_eq!(state.ready.len(), 1);
+        assert!(matches!(
+            state.results[0],
+            Some(Err(CTranslate2Error::Dropped { .. }))
+        ));
+    }
+}
@@ -0,0 +1,169 @@
+use crate::batch_future::{GenerateBatchState, ResultTarget, ResultTargets, ReturnedTargets};
+use crate::generator::group_by_options;
+use crate::{
+    ffi, BatchType, CTranslate2Error, GenerateAsyncContext, GenerateBatchFuture, GenerationOptions,
+    GenerationResult, Generator,
+};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Future returned by `BatchingGenerator::generate`.
+pub struct GenerateFuture(GenerateBatchFuture);
+
+impl Future for GenerateFuture {
+    type Output = Result<GenerationResult, CTranslate2Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.get_mut().0).poll(cx).map(|results| {
+            results.map(|mut results| results.pop().expect("missing generation result"))
+        })
+    }
+}
+
+struct BatchingRequest {
+    tokens: Vec<String>,
+    options: GenerationOptions,
+    target: ResultTarget,
+}
+
+/// Collects the prompts submitted concurrently by many callers and generates them together,
+/// in the spirit of CTranslate2's `BufferedTranslationWrapper`. A batch is submitted once it
+/// holds `max_batch_size` prompts or `max_delay` after its first prompt arrived, whichever
+/// comes first. Prompts with different options are run as separate CTranslate2 batches.
+pub struct BatchingGenerator {
+    generator: Arc<Generator>,
+    sender: Option<mpsc::Sender<BatchingRequest>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl BatchingGenerator {
+    pub fn new(
+        generator: Generator,
+        max_batch_size: usize,
+        max_delay: Duration,
+    ) -> BatchingGenerator {
+        let generator = Arc::new(generator);
+        let (sender, receiver) = mpsc::channel();
+        let worker = {
+            let generator = generator.clone();
+            thread::spawn(move || {
+                BatchingGenerator::run(&generator, receiver, max_batch_size.max(1), max_delay)
+            })
+        };
+        BatchingGenerator {
+            generator,
+            sender: Some(sender),
+            worker: Some(worker),
+        }
+    }
+
+    pub fn generator(&self) -> &Generator {
+        &self.generator
+    }
+
+    /// Queues a prompt, and returns a future which completes with its result once the batch
+    /// it was added to is done, or with `WorkerStopped` if the worker thread is gone.
+    pub fn generate(&self, tokens: Vec<String>, options: GenerationOptions) -> GenerateFuture {
+        let state = GenerateBatchState::new(1);
+        let context = self.generator.context("BatchingGenerator::generate");
+        let error = options.check(context.clone()).err().or_else(|| {
+            let request = BatchingRequest {
+                tokens,
+                options,
+                target: (state.clone(), 0),
+            };
+            self.sender
+                .as_ref()
+                .expect("the batching worker is running")
+                .send(request)
+                .err()
+                .map(|_| CTranslate2Error::WorkerStopped { context })
+        });
+        GenerateFuture(GenerateBatchFuture { state, error })
+    }
+
+    fn run(
+        generator: &Generator,
+        receiver: mpsc::Receiver<BatchingRequest>,
+        max_batch_size: usize,
+        max_delay: Duration,
+    ) {
+        while let Ok(first) = receiver.recv() {
+            let deadline = Instant::now() + max_delay;
+            let mut requests = vec![first];
+            while requests.len() < max_batch_size {
+                match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(request) => requests.push(request),
+                    Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+
+            let requests = requests
+                .into_iter()
+                .map(|request| (request.options, (request.tokens, request.target)));
+            for (options, examples) in group_by_options(requests) {
+                BatchingGenerator::submit(generator, options, examples);
+            }
+        }
+    }
+
+    fn submit(
+        generator: &Generator,
+        options: GenerationOptions,
+        examples: Vec<(Vec<String>, ResultTarget)>,
+    ) {
+        let context = generator.context("BatchingGenerator::generate");
+        let (tokens, targets): (Vec<_>, Vec<_>) = examples.into_iter().unzip();
+        let mut tokens = ffi::VecVecString::new_unique_from(tokens);
+        let returned = ReturnedTargets::default();
+        let result = generator.submit_batch_async(
+            tokens.pin_mut(),
+            0,
+            BatchType::Examples,
+            options.clone(),
+            None,
+            GenerateAsyncContext::new(ResultTargets::Examples(targets), context.clone())
+                .returning_targets(returned.clone()),
+        );
+        if result.is_ok() {
+            return;
+        }
+        // The prompts and their targets are given back when the batch was not submitted.
+        // Errors cannot be cloned: submit the prompts one by one so that each caller gets
+        // the error of its own prompt.
+        let Some(ResultTargets::Examples(targets)) = returned.lock().unwrap().take() else {
+            return;
+        };
+        for (tokens, target) in tokens.to_vec().into_iter().zip(targets) {
+            let result = generator.submit_batch_async(
+                ffi::VecVecString::new_unique_from(vec![tokens]).pin_mut(),
+                0,
+                BatchType::Examples,
+                options.clone(),
+                None,
+                GenerateAsyncContext::new(
+                    ResultTargets::Examples(vec![target.clone()]),
+                    context.clone(),
+                ),
+            );
+            if let Err(error) = result {
+                GenerateBatchState::complete(&target.0, target.1, Err(error));
+            }
+        }
+    }
+}
+
+impl Drop for BatchingGenerator {
+    // Closes the queue and waits for the worker to submit the queued prompts and exit.
+    fn drop(&mut self) {
+        self.sender.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
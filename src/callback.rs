@@ -0,0 +1,161 @@
+use crate::{ffi, CTranslate2Error, ErrorContext, GenerationStepResult};
+use std::any::Any;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+
+pub(crate) type StepCallback<'a> = Box<dyn FnMut(GenerationStepResult) -> bool + Send + 'a>;
+
+// Tokens reported to the step callbacks for one hypothesis, with their log probabilities.
+#[derive(Debug, Default)]
+pub(crate) struct HypothesisLogProbs {
+    cumulative: f32,
+    pub(crate) ids: Vec<usize>,
+    pub(crate) tokens: Vec<f32>,
+}
+
+// Log probabilities reported to the step callbacks, per (input_index, hypothesis_id).
+pub(crate) type TokenLogProbs = Arc<Mutex<HashMap<(usize, usize), HypothesisLogProbs>>>;
+
+// Message of the panic raised by a step callback. It is shared with the context of
+// asynchronous calls, whose results report the panic.
+pub(crate) type CallbackPanic = Arc<Mutex<Option<String>>>;
+
+// The callback can be called concurrently from several CTranslate2 threads, hence the mutex.
+pub struct GenerateCallbackContext {
+    callback: Mutex<StepCallback<'static>>,
+    pub(crate) panic: CallbackPanic,
+    pub(crate) log_probs: TokenLogProbs,
+}
+
+impl GenerateCallbackContext {
+    pub(crate) fn new<F>(callback: F) -> GenerateCallbackContext
+    where
+        F: FnMut(GenerationStepResult) -> bool + Send + 'static,
+    {
+        GenerateCallbackContext {
+            callback: Mutex::new(Box::new(callback)),
+            panic: CallbackPanic::default(),
+            log_probs: TokenLogProbs::default(),
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The context must be dropped before the end of the lifetime `'a`, and must not be used
+    /// by CTranslate2 after that.
+    pub(crate) unsafe fn new_scoped<'a, F>(callback: F) -> GenerateCallbackContext
+    where
+        F: FnMut(GenerationStepResult) -> bool + Send + 'a,
+    {
+        let callback: StepCallback<'a> = Box::new(callback);
+        GenerateCallbackContext {
+            callback: Mutex::new(
+                std::mem::transmute::<StepCallback<'a>, StepCallback<'static>>(callback),
+            ),
+            panic: CallbackPanic::default(),
+            log_probs: TokenLogProbs::default(),
+        }
+    }
+
+    // Unwinding into C++ would abort the process: a panic is caught here instead, stops the
+    // decoding, and is reported once the generation returns.
+    pub(crate) fn call(
+        result: ffi::GenerationStepResult,
+        context: &GenerateCallbackContext,
+    ) -> bool {
+        if context.panic.lock().unwrap().is_some() {
+            return true;
+        }
+        let log_prob = result.log_prob_valid.then_some(result.log_prob);
+        let cumulative_log_prob = log_prob.map(|log_prob| {
+            let mut log_probs = context.log_probs.lock().unwrap();
+            let hypothesis = log_probs
+                .entry((result.input_index, result.hypothesis_id))
+                .or_default();
+            hypothesis.cumulative += log_prob;
+            hypothesis.ids.push(result.token_id);
+            hypothesis.tokens.push(log_prob);
+            hypothesis.cumulative
+        });
+        let result = GenerationStepResult {
+            step: result.step,
+            batch_id: result.batch_id,
+            input_index: result.input_index,
+            hypothesis_id: result.hypothesis_id,
+            token_id: result.token_id,
+            token: result.token,
+            log_prob,
+            cumulative_log_prob,
+            is_last: result.is_last,
+        };
+        let mut callback = context.callback.lock().unwrap();
+        match panic::catch_unwind(AssertUnwindSafe(|| callback(result))) {
+            Ok(stop) => stop,
+            Err(payload) => {
+                *context.panic.lock().unwrap() = Some(panic_message(payload));
+                true
+            }
+        }
+    }
+
+    pub(crate) fn into_result<T>(
+        self,
+        context: ErrorContext,
+        result: Result<T, CTranslate2Error>,
+    ) -> Result<T, CTranslate2Error> {
+        match self.panic.lock().unwrap().take() {
+            Some(message) => Err(CTranslate2Error::CallbackPanic { context, message }),
+            None => result,
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "Box<dyn Any>".to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, ffi_step};
+
+    #[test]
+    fn panic_stops_the_decoding_and_is_reported() {
+        let context = GenerateCallbackContext::new(|_| panic!("callback failed"));
+        assert!(GenerateCallbackContext::call(ffi_step(), &context));
+        // The callback is not called again once it panicked.
+        assert!(GenerateCallbackContext::call(ffi_step(), &context));
+
+        match context.into_result(testing::context(), Ok(())) {
+            Err(CTranslate2Error::CallbackPanic { message, .. }) => {
+                assert_eq!(message, "callback failed")
+            }
+            result => panic!("unexpected result {result:?}"),
+        }
+    }
+
+    #[test]
+    fn log_probs_are_accumulated_per_hypothesis() {
+        let context = GenerateCallbackContext::new(|step| {
+            assert_eq!(
+                step.cumulative_log_prob,
+                Some(step.log_prob.unwrap() * (step.step + 1) as f32)
+            );
+            false
+        });
+        for index in 0..2 {
+            let mut step = ffi_step();
+            step.step = index;
+            assert!(!GenerateCallbackContext::call(step, &context));
+        }
+        let log_probs = context.log_probs.lock().unwrap();
+        assert_eq!(log_probs[&(0, 0)].tokens, vec![-0.5, -0.5]);
+    }
+}
@@ -0,0 +1,112 @@
+use crate::{CTranslate2Error, ErrorContext};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Stops a generation from another thread (e.g. when the client disconnected), or once a
+/// deadline is reached. Clones share the same cancellation flag.
+///
+/// A running batch is stopped between two decoding steps, which are only reported with greedy
+/// search and sampling: the generation calls reject a token combined with `beam_size > 1`.
+/// The call then fails with `Cancelled` or `TimedOut`, and the partial hypotheses are dropped.
+///
+/// `Generator::generate_batch`, `generate_batch_ids` and the `_async` calls take a token. The
+/// token streams (`generate_tokens` and its variants) are stopped by dropping them instead, and
+/// `BatchingGenerator::generate` takes none since its prompt shares a batch with other callers.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Returns a token sharing the cancellation flag of `self` which also expires at `deadline`.
+    pub fn with_deadline(&self, deadline: Instant) -> CancellationToken {
+        CancellationToken {
+            cancelled: self.cancelled.clone(),
+            deadline: Some(deadline),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    pub(crate) fn should_stop(&self) -> bool {
+        self.is_cancelled() || self.is_expired()
+    }
+
+    pub(crate) fn check(&self, context: ErrorContext) -> Result<(), CTranslate2Error> {
+        if self.is_cancelled() {
+            Err(CTranslate2Error::Cancelled { context })
+        } else if self.is_expired() {
+            Err(CTranslate2Error::TimedOut { context })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::context;
+    use std::time::Duration;
+
+    #[test]
+    fn new_token_does_not_stop() {
+        let token = CancellationToken::new();
+        assert!(!token.should_stop());
+        assert!(token.check(context()).is_ok());
+    }
+
+    #[test]
+    fn clones_share_the_cancellation() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        clone.cancel();
+        assert!(token.is_cancelled());
+        assert!(matches!(
+            token.check(context()),
+            Err(CTranslate2Error::Cancelled { .. })
+        ));
+    }
+
+    #[test]
+    fn deadline_expires() {
+        let token = CancellationToken::new();
+        let expired = token.with_deadline(Instant::now() - Duration::from_secs(1));
+        assert!(!token.should_stop());
+        assert!(expired.should_stop());
+        assert!(matches!(
+            expired.check(context()),
+            Err(CTranslate2Error::TimedOut { .. })
+        ));
+
+        let pending = token.with_deadline(Instant::now() + Duration::from_secs(3600));
+        assert!(!pending.should_stop());
+        // The cancellation is still shared with the token the deadline was added to.
+        token.cancel();
+        assert!(matches!(
+            pending.check(context()),
+            Err(CTranslate2Error::Cancelled { .. })
+        ));
+    }
+}
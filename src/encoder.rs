@@ -0,0 +1,152 @@
+use crate::loader::{LoadOptions, LoadedModel};
+use crate::{
+    ffi, CTranslate2Error, ComputeType, Device, ErrorContext, LoadSummary, ModelReader, ParseError,
+    StorageView,
+};
+use cxx::UniquePtr;
+
+pub struct EncoderForwardOutput {
+    /// Output of the last encoder layer, with shape `[batch_size, max_length, hidden_size]`.
+    pub last_hidden_state: StorageView,
+    /// Output of the pooling layer, when the model has one.
+    pub pooler_output: Option<StorageView>,
+}
+
+pub struct Encoder {
+    encoder: UniquePtr<ffi::EncoderWrapper>,
+    model_path: String,
+    load_summary: LoadSummary,
+}
+
+impl Encoder {
+    pub fn new(
+        model_path: &str,
+        device: Device,
+        device_indicies: &[i32],
+        compute_type: ComputeType,
+        inter_threads: usize,
+        intra_threads: usize,
+        max_queued_batches: i32,
+    ) -> Result<Encoder, CTranslate2Error> {
+        LoadOptions {
+            device,
+            device_indicies,
+            compute_type,
+            inter_threads,
+            intra_threads,
+            max_queued_batches,
+        }
+        .load_path("Encoder::new", model_path)
+        .map(Encoder::from_loaded)
+    }
+
+    /// Loads a model whose files are provided by a custom `ModelReader`.
+    pub fn from_reader<R: ModelReader + 'static>(
+        reader: R,
+        device: Device,
+        device_indicies: &[i32],
+        compute_type: ComputeType,
+        inter_threads: usize,
+        intra_threads: usize,
+        max_queued_batches: i32,
+    ) -> Result<Encoder, CTranslate2Error> {
+        LoadOptions {
+            device,
+            device_indicies,
+            compute_type,
+            inter_threads,
+            intra_threads,
+            max_queued_batches,
+        }
+        .load_reader("Encoder::from_reader", reader)
+        .map(Encoder::from_loaded)
+    }
+
+    /// Same as `Encoder::new`, but the model files are read through memory maps, see
+    /// `MmapModelReader`.
+    #[cfg(feature = "mmap")]
+    pub fn from_mmap(
+        model_path: &str,
+        device: Device,
+        device_indicies: &[i32],
+        compute_type: ComputeType,
+        inter_threads: usize,
+        intra_threads: usize,
+        max_queued_batches: i32,
+    ) -> Result<Encoder, CTranslate2Error> {
+        LoadOptions {
+            device,
+            device_indicies,
+            compute_type,
+            inter_threads,
+            intra_threads,
+            max_queued_batches,
+        }
+        .load_mmap("Encoder::from_mmap", model_path)
+        .map(Encoder::from_loaded)
+    }
+
+    fn from_loaded(model: LoadedModel<ffi::EncoderWrapper>) -> Encoder {
+        Encoder {
+            encoder: model.wrapper,
+            model_path: model.model_path,
+            load_summary: model.load_summary,
+        }
+    }
+
+    /// Time and memory used to load the model, see `LoadSummary`.
+    pub fn load_summary(&self) -> &LoadSummary {
+        &self.load_summary
+    }
+
+    fn context(&self, call: &'static str) -> ErrorContext {
+        ErrorContext::new(call, &self.model_path)
+    }
+
+    pub fn device(&self) -> Result<Device, ParseError> {
+        self.encoder.device().parse()
+    }
+
+    pub fn num_replicas(&self) -> usize {
+        self.encoder.num_replicas()
+    }
+
+    pub fn num_queued_batches(&self) -> usize {
+        self.encoder.num_queued_batches()
+    }
+
+    pub fn num_active_batches(&self) -> usize {
+        self.encoder.num_active_batches()
+    }
+
+    pub fn forward_batch(
+        &self,
+        tokens: Vec<Vec<String>>,
+        token_type_ids: Option<Vec<Vec<usize>>>,
+    ) -> Result<EncoderForwardOutput, CTranslate2Error> {
+        let output = self
+            .encoder
+            .forward_batch(
+                ffi::VecVecString::new_unique_from(tokens),
+                match token_type_ids {
+                    Some(token_type_ids) => ffi::VecVecUsize::new_unique_from(token_type_ids),
+                    None => UniquePtr::null(),
+                },
+            )
+            .map_err(|ex| {
+                CTranslate2Error::from_exception(self.context("Encoder::forward_batch"), ex)
+            })?;
+        Ok(EncoderForwardOutput {
+            last_hidden_state: StorageView {
+                view: output.last_hidden_state,
+            },
+            pooler_output: if output.pooler_output.is_null() {
+                None
+            } else {
+                Some(StorageView {
+                    view: output.pooler_output,
+                })
+            },
+        })
+    }
+}
@@ -0,0 +1,197 @@
+//! Model directories whose files are encrypted with AES-256-GCM.
+//!
+//! Each file keeps its name and holds a random 12-byte nonce followed by the ciphertext and
+//! the authentication tag. The file name is authenticated as well, so files cannot be swapped
+//! within a model.
+
+use crate::ModelReader;
+use aes_gcm::aead::{AeadCore, AeadInPlace, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce, Tag};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use zeroize::Zeroizing;
+
+const NONCE_SIZE: usize = 12;
+const TAG_SIZE: usize = 16;
+
+/// Reads a model directory produced by `encrypt_model`, decrypting the files in memory so
+/// that no plaintext is written to disk. Pass it to `Generator::from_reader`.
+///
+/// A file must be authenticated as a whole before any of it is returned, so each file is read
+/// into memory and decrypted in place: loading needs a buffer as large as the largest file
+/// (usually `model.bin`) on top of the memory of the loaded model. The buffer is zeroed when
+/// the reader of the file is dropped.
+pub struct EncryptedModelReader {
+    model_path: String,
+    cipher: Aes256Gcm,
+}
+
+impl EncryptedModelReader {
+    pub fn new(model_path: &str, key: &[u8; 32]) -> EncryptedModelReader {
+        EncryptedModelReader {
+            model_path: model_path.to_string(),
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)),
+        }
+    }
+}
+
+impl ModelReader for EncryptedModelReader {
+    fn model_id(&self) -> String {
+        self.model_path.clone()
+    }
+
+    fn get_file(&self, filename: &str) -> io::Result<Option<Box<dyn Read + '_>>> {
+        let content = match std::fs::read(Path::new(&self.model_path).join(filename)) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        // The buffer is cleared once read, since it holds the plaintext.
+        let mut content = Zeroizing::new(content);
+        if content.len() < NONCE_SIZE + TAG_SIZE {
+            return Err(decryption_error(filename));
+        }
+        // Decrypt in place and read the plaintext where it is, to avoid another copy of the
+        // weights.
+        let (nonce, rest) = content.split_at_mut(NONCE_SIZE);
+        let (ciphertext, tag) = rest.split_at_mut(rest.len() - TAG_SIZE);
+        let plaintext_len = ciphertext.len() as u64;
+        self.cipher
+            .decrypt_in_place_detached(
+                Nonce::from_slice(nonce),
+                filename.as_bytes(),
+                ciphertext,
+                Tag::from_slice(tag),
+            )
+            .map_err(|_| decryption_error(filename))?;
+        let mut plaintext = io::Cursor::new(content);
+        plaintext.set_position(NONCE_SIZE as u64);
+        Ok(Some(Box::new(plaintext.take(plaintext_len))))
+    }
+}
+
+fn decryption_error(filename: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("cannot decrypt {filename}: wrong key or corrupted file"),
+    )
+}
+
+/// Generates a random key for `encrypt_model`.
+pub fn generate_key() -> [u8; 32] {
+    Aes256Gcm::generate_key(&mut OsRng).into()
+}
+
+/// Encrypts the files of the model directory `model_path` into `output_path`, which is
+/// created if needed.
+pub fn encrypt_model(model_path: &str, output_path: &str, key: &[u8; 32]) -> io::Result<()> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    std::fs::create_dir_all(output_path)?;
+    for entry in std::fs::read_dir(model_path)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let filename = entry.file_name().to_string_lossy().into_owned();
+        // The buffer holds the plaintext, and is sized up front so that it is never reallocated
+        // and every copy of it is zeroed.
+        let mut file = std::fs::File::open(entry.path())?;
+        let mut content = Zeroizing::new(Vec::new());
+        content.reserve_exact(file.metadata()?.len() as usize + TAG_SIZE);
+        file.read_to_end(&mut content)?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let tag = cipher
+            .encrypt_in_place_detached(&nonce, filename.as_bytes(), &mut content)
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("cannot encrypt {filename}: file too large"),
+                )
+            })?;
+        let mut output = std::fs::File::create(Path::new(output_path).join(&filename))?;
+        output.write_all(&nonce)?;
+        output.write_all(&content)?;
+        output.write_all(&tag)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+    use std::path::PathBuf;
+
+    // A model directory encrypted with `key`, and the path of the encrypted copy in `dir`.
+    fn encrypted_model(dir: &TempDir, key: &[u8; 32]) -> PathBuf {
+        let model_path = dir.join("model");
+        std::fs::create_dir(&model_path).unwrap();
+        std::fs::write(model_path.join("config.json"), b"{}").unwrap();
+        std::fs::write(model_path.join("model.bin"), [7u8; 1000]).unwrap();
+        let output_path = dir.join("encrypted");
+        encrypt_model(
+            model_path.to_str().unwrap(),
+            output_path.to_str().unwrap(),
+            key,
+        )
+        .unwrap();
+        output_path
+    }
+
+    fn read_file(reader: &EncryptedModelReader, filename: &str) -> io::Result<Option<Vec<u8>>> {
+        let Some(mut file) = reader.get_file(filename)? else {
+            return Ok(None);
+        };
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)?;
+        Ok(Some(content))
+    }
+
+    #[test]
+    fn encrypted_files_roundtrip() {
+        let key = generate_key();
+        let dir = TempDir::new("encryption-roundtrip");
+        let path = encrypted_model(&dir, &key);
+        assert_ne!(std::fs::read(path.join("model.bin")).unwrap(), [7u8; 1000]);
+
+        let reader = EncryptedModelReader::new(path.to_str().unwrap(), &key);
+        assert_eq!(read_file(&reader, "config.json").unwrap().unwrap(), b"{}");
+        assert_eq!(
+            read_file(&reader, "model.bin").unwrap().unwrap(),
+            [7u8; 1000]
+        );
+        assert!(read_file(&reader, "vocabulary.txt").unwrap().is_none());
+    }
+
+    #[test]
+    fn wrong_key_is_rejected() {
+        let dir = TempDir::new("encryption-wrong-key");
+        let path = encrypted_model(&dir, &generate_key());
+        let reader = EncryptedModelReader::new(path.to_str().unwrap(), &generate_key());
+        let error = read_file(&reader, "model.bin").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn tampered_files_are_rejected() {
+        let key = generate_key();
+        let dir = TempDir::new("encryption-tampered");
+        let path = encrypted_model(&dir, &key);
+        let reader = EncryptedModelReader::new(path.to_str().unwrap(), &key);
+
+        let mut content = std::fs::read(path.join("model.bin")).unwrap();
+        content[NONCE_SIZE + 10] ^= 1;
+        std::fs::write(path.join("model.bin"), &content).unwrap();
+        let error = read_file(&reader, "model.bin").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        // The file name is authenticated, so files cannot be swapped.
+        std::fs::copy(path.join("config.json"), path.join("vocabulary.txt")).unwrap();
+        let error = read_file(&reader, "vocabulary.txt").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        std::fs::write(path.join("config.json"), [0u8; NONCE_SIZE]).unwrap();
+        let error = read_file(&reader, "config.json").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
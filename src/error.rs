@@ -0,0 +1,224 @@
+use crate::ffi;
+use std::error::Error;
+use std::{fmt, io};
+
+/// The call that failed and, for calls made on a model, the path it was loaded from.
+#[derive(Clone, Debug)]
+pub struct ErrorContext {
+    /// Name of the failing call, e.g. `Generator::generate_batch`.
+    pub call: &'static str,
+    pub model_path: Option<String>,
+}
+
+impl ErrorContext {
+    pub(crate) fn new(call: &'static str, model_path: &str) -> ErrorContext {
+        ErrorContext {
+            call,
+            model_path: Some(model_path.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.model_path {
+            Some(model_path) => write!(f, "{} (model {})", self.call, model_path),
+            None => f.write_str(self.call),
+        }
+    }
+}
+
+/// Errors returned by the models. The message raised by CTranslate2, when there is one, is
+/// available through `Error::source`.
+#[derive(Debug)]
+pub enum CTranslate2Error {
+    /// The model files could not be found, read or are not a supported model.
+    ModelLoad {
+        context: ErrorContext,
+        source: cxx::Exception,
+    },
+    /// The model configuration (e.g. `config.json`) is invalid.
+    InvalidConfig {
+        context: ErrorContext,
+        source: cxx::Exception,
+    },
+    /// The device or compute type is not supported by this build or by the hardware.
+    UnsupportedDevice {
+        context: ErrorContext,
+        source: cxx::Exception,
+    },
+    /// A host or device allocation failed.
+    OutOfMemory {
+        context: ErrorContext,
+        source: cxx::Exception,
+    },
+    /// The inputs or options were rejected by CTranslate2.
+    InvalidInput {
+        context: ErrorContext,
+        source: cxx::Exception,
+    },
+    /// The options were rejected before calling CTranslate2.
+    InvalidOptions {
+        context: ErrorContext,
+        reason: String,
+    },
+    /// A step callback panicked, with the given panic message.
+    CallbackPanic {
+        context: ErrorContext,
+        message: String,
+    },
+    /// The call was cancelled through a `CancellationToken`, before it started or while decoding.
+    Cancelled { context: ErrorContext },
+    /// The deadline of the `CancellationToken` passed, before the call started or while decoding.
+    TimedOut { context: ErrorContext },
+    /// The model was dropped before the asynchronous call completed.
+    Dropped { context: ErrorContext },
+    /// The worker thread of a `BatchingGenerator` stopped, so the prompt could not be queued.
+    WorkerStopped { context: ErrorContext },
+    /// Any other error raised by CTranslate2.
+    Backend {
+        context: ErrorContext,
+        source: cxx::Exception,
+    },
+    /// The model files could not be read before being handed to CTranslate2.
+    Io {
+        context: ErrorContext,
+        source: io::Error,
+    },
+    /// The values of a `StorageView` could not be converted to an `ndarray` array.
+    #[cfg(feature = "ndarray")]
+    Shape {
+        context: ErrorContext,
+        source: ndarray::ShapeError,
+    },
+}
+
+impl CTranslate2Error {
+    pub fn context(&self) -> &ErrorContext {
+        match self {
+            CTranslate2Error::ModelLoad { context, .. }
+            | CTranslate2Error::InvalidConfig { context, .. }
+            | CTranslate2Error::UnsupportedDevice { context, .. }
+            | CTranslate2Error::OutOfMemory { context, .. }
+            | CTranslate2Error::InvalidInput { context, .. }
+            | CTranslate2Error::InvalidOptions { context, .. }
+            | CTranslate2Error::CallbackPanic { context, .. }
+            | CTranslate2Error::Cancelled { context }
+            | CTranslate2Error::TimedOut { context }
+            | CTranslate2Error::Dropped { context }
+            | CTranslate2Error::WorkerStopped { context }
+            | CTranslate2Error::Backend { context, .. }
+            | CTranslate2Error::Io { context, .. } => context,
+            #[cfg(feature = "ndarray")]
+            CTranslate2Error::Shape { context, .. } => context,
+        }
+    }
+
+    // Classifies an exception raised while loading a model, from the kind given by the C++
+    // loader according to the type of the exception. Must be called on the thread that
+    // received the exception, right after it.
+    pub(crate) fn from_load_exception(
+        context: ErrorContext,
+        source: cxx::Exception,
+    ) -> CTranslate2Error {
+        match ffi::last_exception_kind().as_str() {
+            "bad_alloc" => CTranslate2Error::OutOfMemory { context, source },
+            "unsupported_device" => CTranslate2Error::UnsupportedDevice { context, source },
+            "invalid_config" => CTranslate2Error::InvalidConfig { context, source },
+            "model_load" => CTranslate2Error::ModelLoad { context, source },
+            _ => CTranslate2Error::Backend { context, source },
+        }
+    }
+
+    // Classifies an exception raised by a call on a loaded model. Must be called on the
+    // thread that received the exception, right after it.
+    pub(crate) fn from_exception(
+        context: ErrorContext,
+        source: cxx::Exception,
+    ) -> CTranslate2Error {
+        let kind = ffi::last_exception_kind();
+        if kind == "bad_alloc" || source.what().to_lowercase().contains("out of memory") {
+            CTranslate2Error::OutOfMemory { context, source }
+        } else if kind == "invalid_argument" || kind == "out_of_range" {
+            CTranslate2Error::InvalidInput { context, source }
+        } else {
+            CTranslate2Error::Backend { context, source }
+        }
+    }
+}
+
+impl fmt::Display for CTranslate2Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CTranslate2Error::ModelLoad { context, .. } => {
+                write!(f, "{context}: failed to load the model")
+            }
+            CTranslate2Error::InvalidConfig { context, .. } => {
+                write!(f, "{context}: invalid model configuration")
+            }
+            CTranslate2Error::UnsupportedDevice { context, .. } => {
+                write!(f, "{context}: unsupported device or compute type")
+            }
+            CTranslate2Error::OutOfMemory { context, .. } => write!(f, "{context}: out of memory"),
+            CTranslate2Error::InvalidInput { context, .. } => write!(f, "{context}: invalid input"),
+            CTranslate2Error::InvalidOptions { context, reason } => {
+                write!(f, "{context}: invalid options: {reason}")
+            }
+            CTranslate2Error::CallbackPanic { context, message } => {
+                write!(f, "{context}: step callback panicked: {message}")
+            }
+            CTranslate2Error::Cancelled { context } => write!(f, "{context}: cancelled"),
+            CTranslate2Error::TimedOut { context } => write!(f, "{context}: deadline exceeded"),
+            CTranslate2Error::Dropped { context } => {
+                write!(
+                    f,
+                    "{context}: the model was dropped before the call completed"
+                )
+            }
+            CTranslate2Error::WorkerStopped { context } => {
+                write!(f, "{context}: the batching worker stopped")
+            }
+            CTranslate2Error::Backend { context, .. } => write!(f, "{context}: CTranslate2 error"),
+            CTranslate2Error::Io { context, .. } => {
+                write!(f, "{context}: failed to read the model files")
+            }
+            #[cfg(feature = "ndarray")]
+            CTranslate2Error::Shape { context, .. } => {
+                write!(f, "{context}: the storage does not match its shape")
+            }
+        }
+    }
+}
+
+impl Error for CTranslate2Error {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CTranslate2Error::ModelLoad { source, .. }
+            | CTranslate2Error::InvalidConfig { source, .. }
+            | CTranslate2Error::UnsupportedDevice { source, .. }
+            | CTranslate2Error::OutOfMemory { source, .. }
+            | CTranslate2Error::InvalidInput { source, .. }
+            | CTranslate2Error::Backend { source, .. } => Some(source),
+            CTranslate2Error::Io { source, .. } => Some(source),
+            #[cfg(feature = "ndarray")]
+            CTranslate2Error::Shape { source, .. } => Some(source),
+            CTranslate2Error::InvalidOptions { .. }
+            | CTranslate2Error::CallbackPanic { .. }
+            | CTranslate2Error::Cancelled { .. }
+            | CTranslate2Error::TimedOut { .. }
+            | CTranslate2Error::Dropped { .. }
+            | CTranslate2Error::WorkerStopped { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseError(pub(crate) String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for ParseError {}
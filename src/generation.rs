@@ -0,0 +1,534 @@
+use crate::callback::{HypothesisLogProbs, TokenLogProbs};
+use crate::{ffi, CTranslate2Error, ErrorContext};
+
+/// Decoding options of `Generator`. They can be built field by field from `Default`, or with
+/// `GenerationOptions::builder()` which also validates them.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct GenerationOptions {
+    /// Beam size to use for beam search (set 1 to run greedy search).
+    pub beam_size: usize,
+    /// Beam search patience factor, as described in https://arxiv.org/abs/2204.05424.
+    /// The decoding will continue until beam_size*patience hypotheses are finished.
+    pub patience: f32,
+    /// Exponential penalty applied to the length during beam search.
+    /// The scores are normalized with:
+    ///   hypothesis_score /= (hypothesis_length ** length_penalty)
+    pub length_penalty: f32,
+    /// Penalty applied to the score of previously generated tokens, as described in
+    /// https://arxiv.org/abs/1909.05858 (set > 1 to penalize).
+    pub repetition_penalty: f32,
+    /// Prevent repetitions of ngrams with this size (set 0 to disable).
+    pub no_repeat_ngram_size: usize,
+    /// Disable the generation of the unknown token.
+    pub disable_unk: bool,
+    /// Disable the generation of some sequences of tokens.
+    pub suppress_sequences: Vec<Vec<String>>,
+    /// Stop the decoding on one of these tokens
+    pub end_token: Vec<String>,
+    /// If end_token is empty, top on the EOS token
+    pub empty_end_token_means_stop_on_eos_token: bool,
+    /// Include the end token in the result.
+    pub return_end_token: bool,
+    /// Include the log probability of each token in the step results.
+    pub return_log_prob: bool,
+    /// Max length constraint
+    pub max_length: usize,
+    /// Min length constraint
+    pub min_length: usize,
+    /// Randomly sample from the top K candidates (set 0 to sample from the full output distribution).
+    pub sampling_topk: usize,
+    /// Keep the most probable tokens whose cumulative probability exceeds this value.
+    pub sampling_topp: f32,
+    /// High temperature increase randomness.
+    pub sampling_temperature: f32,
+    /// Number of hypotheses to include in the result.
+    pub num_hypotheses: usize,
+    /// Include scores in the result.
+    pub return_scores: bool,
+    /// Return alternatives at the first unconstrained decoding position. This is typically
+    /// used with a prefix to provide alternatives at a specifc location.
+    pub return_alternatives: bool,
+    /// Minimum probability to expand an alternative.
+    pub min_alternative_expansion_prob: f32,
+    /// The static prompt will prefix all inputs for this model.
+    pub static_prompt: Vec<String>,
+    /// Cache the model state after the static prompt and reuse it for future runs using
+    /// the same static prompt.
+    pub cache_static_prompt: bool,
+    /// Include the input tokens in the generation result.
+    pub include_prompt_in_result: bool,
+}
+
+impl Default for GenerationOptions {
+    fn default() -> GenerationOptions {
+        GenerationOptions {
+            beam_size: 1,
+            patience: 1.,
+            length_penalty: 1.,
+            repetition_penalty: 1.,
+            no_repeat_ngram_size: 0,
+            disable_unk: false,
+            suppress_sequences: Vec::new(),
+            end_token: Vec::new(),
+            empty_end_token_means_stop_on_eos_token: true,
+            return_end_token: false,
+            return_log_prob: false,
+            max_length: 512,
+            min_length: 0,
+            sampling_topk: 1,
+            sampling_topp: 1.,
+            sampling_temperature: 1.,
+            num_hypotheses: 1,
+            return_scores: false,
+            return_alternatives: false,
+            min_alternative_expansion_prob: 0.,
+            static_prompt: Vec::new(),
+            cache_static_prompt: true,
+            include_prompt_in_result: true,
+        }
+    }
+}
+
+impl GenerationOptions {
+    pub fn builder() -> GenerationOptionsBuilder {
+        GenerationOptionsBuilder::default()
+    }
+
+    /// Rejects the combinations that CTranslate2 would reject or silently misbehave on.
+    pub fn validate(&self) -> Result<(), CTranslate2Error> {
+        self.check(ErrorContext {
+            call: "GenerationOptions::validate",
+            model_path: None,
+        })
+    }
+
+    pub(crate) fn check(&self, context: ErrorContext) -> Result<(), CTranslate2Error> {
+        let reason = if self.beam_size == 0 {
+            "beam_size must be at least 1"
+        } else if self.num_hypotheses == 0 {
+            "num_hypotheses must be at least 1"
+        } else if self.min_length > self.max_length {
+            "min_length must not be greater than max_length"
+        } else if !(self.sampling_topp > 0. && self.sampling_topp <= 1.) {
+            "sampling_topp must be in (0, 1]"
+        } else if self.sampling_temperature.is_nan() || self.sampling_temperature <= 0. {
+            "sampling_temperature must be positive"
+        } else if self.patience.is_nan() || self.patience <= 0. {
+            "patience must be positive"
+        } else if self.repetition_penalty.is_nan() || self.repetition_penalty <= 0. {
+            "repetition_penalty must be positive"
+        } else if !(0. ..=1.).contains(&self.min_alternative_expansion_prob) {
+            "min_alternative_expansion_prob must be in [0, 1]"
+        } else {
+            return Ok(());
+        };
+        Err(CTranslate2Error::InvalidOptions {
+            context,
+            reason: reason.to_string(),
+        })
+    }
+
+    pub(crate) fn into_ffi(self) -> ffi::GenerationOptions {
+        ffi::GenerationOptions {
+            beam_size: self.beam_size,
+            patience: self.patience,
+            length_penalty: self.length_penalty,
+            repetition_penalty: self.repetition_penalty,
+            no_repeat_ngram_size: self.no_repeat_ngram_size,
+            disable_unk: self.disable_unk,
+            suppress_sequences: ffi::VecVecString::new_unique_from(self.suppress_sequences),
+            end_token: self.end_token,
+            empty_end_token_means_stop_on_eos_token: self.empty_end_token_means_stop_on_eos_token,
+            return_end_token: self.return_end_token,
+            return_log_prob: self.return_log_prob,
+            max_length: self.max_length,
+            min_length: self.min_length,
+            sampling_topk: self.sampling_topk,
+            sampling_topp: self.sampling_topp,
+            sampling_temperature: self.sampling_temperature,
+            num_hypotheses: self.num_hypotheses,
+            return_scores: self.return_scores,
+            return_alternatives: self.return_alternatives,
+            min_alternative_expansion_prob: self.min_alternative_expansion_prob,
+            static_prompt: self.static_prompt,
+            cache_static_prompt: self.cache_static_prompt,
+            include_prompt_in_result: self.include_prompt_in_result,
+        }
+    }
+}
+
+macro_rules! setter {
+    ($name:ident: $type:ty) => {
+        pub fn $name(mut self, $name: $type) -> Self {
+            self.options.$name = $name;
+            self
+        }
+    };
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct GenerationOptionsBuilder {
+    options: GenerationOptions,
+}
+
+impl GenerationOptionsBuilder {
+    setter!(beam_size: usize);
+    setter!(patience: f32);
+    setter!(length_penalty: f32);
+    setter!(repetition_penalty: f32);
+    setter!(no_repeat_ngram_size: usize);
+    setter!(disable_unk: bool);
+    setter!(suppress_sequences: Vec<Vec<String>>);
+    setter!(end_token: Vec<String>);
+    setter!(empty_end_token_means_stop_on_eos_token: bool);
+    setter!(return_end_token: bool);
+    setter!(return_log_prob: bool);
+    setter!(max_length: usize);
+    setter!(min_length: usize);
+    setter!(sampling_topk: usize);
+    setter!(sampling_topp: f32);
+    setter!(sampling_temperature: f32);
+    setter!(num_hypotheses: usize);
+    setter!(return_scores: bool);
+    setter!(return_alternatives: bool);
+    setter!(min_alternative_expansion_prob: f32);
+    setter!(static_prompt: Vec<String>);
+    setter!(cache_static_prompt: bool);
+    setter!(include_prompt_in_result: bool);
+
+    pub fn build(self) -> Result<GenerationOptions, CTranslate2Error> {
+        self.options.validate()?;
+        Ok(self.options)
+    }
+}
+
+/// A token generated by `Generator::generate_batch` callbacks and token streams.
+#[derive(Clone, Debug)]
+pub struct GenerationStepResult {
+    pub step: usize,
+    /// Index of the example in the batch run by CTranslate2.
+    pub batch_id: usize,
+    /// Index of the example in the input of the call.
+    pub input_index: usize,
+    /// Index of the hypothesis, when several are generated for the same example.
+    pub hypothesis_id: usize,
+    pub token_id: usize,
+    pub token: String,
+    /// Log probability of the token, when `return_log_prob` is set.
+    pub log_prob: Option<f32>,
+    /// Sum of the log probabilities of the hypothesis so far, when `return_log_prob` is set.
+    pub cumulative_log_prob: Option<f32>,
+    pub is_last: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Hypothesis {
+    pub tokens: Vec<String>,
+    pub ids: Vec<usize>,
+    /// Score of the hypothesis, when `return_scores` is set.
+    pub score: Option<f32>,
+    /// Log probability of each generated token, when `return_log_prob` is set. Only available
+    /// with greedy search and sampling. The end token is included only with `return_end_token`.
+    pub token_log_probs: Option<Vec<f32>>,
+}
+
+/// Hypotheses generated for one example, from the best to the worst.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GenerationResult {
+    pub hypotheses: Vec<Hypothesis>,
+}
+
+impl GenerationResult {
+    // `log_probs` holds the token log probabilities collected by the step callbacks, if any.
+    pub(crate) fn from_ffi(
+        result: ffi::GenerationResult,
+        input_index: usize,
+        log_probs: Option<&TokenLogProbs>,
+    ) -> GenerationResult {
+        let sequence_ids = result.sequence_ids.to_vec();
+        let mut token_log_probs = match log_probs {
+            Some(log_probs) => {
+                let mut log_probs = log_probs.lock().unwrap();
+                let mut keys: Vec<_> = log_probs
+                    .keys()
+                    .filter(|(index, _)| *index == input_index)
+                    .copied()
+                    .collect();
+                keys.sort_unstable();
+                let collected = keys
+                    .into_iter()
+                    .filter_map(|key| log_probs.remove(&key))
+                    .collect();
+                match_log_probs(&sequence_ids, collected)
+            }
+            None => vec![None; sequence_ids.len()],
+        };
+        let hypotheses = result
+            .sequences
+            .to_vec()
+            .into_iter()
+            .zip(sequence_ids)
+            .enumerate()
+            .map(|(index, (tokens, ids))| Hypothesis {
+                tokens,
+                ids,
+                score: result.scores.get(index).copied(),
+                token_log_probs: token_log_probs.get_mut(index).and_then(Option::take),
+            })
+            .collect();
+        GenerationResult { hypotheses }
+    }
+
+    pub(crate) fn from_ffi_batch(
+        results: Vec<ffi::GenerationResult>,
+        log_probs: Option<&TokenLogProbs>,
+    ) -> Vec<GenerationResult> {
+        results
+            .into_iter()
+            .enumerate()
+            .map(|(index, result)| GenerationResult::from_ffi(result, index, log_probs))
+            .collect()
+    }
+}
+
+// Assigns the log probabilities collected for each hypothesis, in `hypothesis_id` order, to
+// the final hypotheses, which CTranslate2 sorts by score when it generates several. A final
+// hypothesis ends with the tokens of its steps: it can start with the prompt, and the end token
+// of the last step is only kept with `return_end_token`.
+fn match_log_probs(
+    sequence_ids: &[Vec<usize>],
+    mut collected: Vec<HypothesisLogProbs>,
+) -> Vec<Option<Vec<f32>>> {
+    let mut used = vec![false; collected.len()];
+    let mut find = |ids: &[usize], drop_end_token: bool| {
+        let index = (0..collected.len()).find(|&index| {
+            let steps = &collected[index].ids;
+            let steps = match drop_end_token {
+                true => match steps.split_last() {
+                    Some((_, steps)) => steps,
+                    None => return false,
+                },
+                false => steps,
+            };
+            !used[index] && ids.ends_with(steps)
+        })?;
+        used[index] = true;
+        let mut log_probs = std::mem::take(&mut collected[index].tokens);
+        if drop_end_token {
+            log_probs.pop();
+        }
+        Some(log_probs)
+    };
+    let mut matched: Vec<_> = sequence_ids.iter().map(|ids| find(ids, false)).collect();
+    for (ids, log_probs) in sequence_ids.iter().zip(&mut matched) {
+        if log_probs.is_none() {
+            *log_probs = find(ids, true);
+        }
+    }
+    matched
+}
+
+impl IntoIterator for GenerationResult {
+    type Item = Hypothesis;
+    type IntoIter = std::vec::IntoIter<Hypothesis>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.hypotheses.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a GenerationResult {
+    type Item = &'a Hypothesis;
+    type IntoIter = std::slice::Iter<'a, Hypothesis>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.hypotheses.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::context;
+
+    fn reason(builder: GenerationOptionsBuilder) -> String {
+        match builder.build() {
+            Err(CTranslate2Error::InvalidOptions { reason, .. }) => reason,
+            result => panic!("unexpected result {result:?}"),
+        }
+    }
+
+    #[test]
+    fn builder_sets_the_options() {
+        let options = GenerationOptions::builder()
+            .beam_size(4)
+            .max_length(16)
+            .sampling_topk(10)
+            .end_token(vec!["</s>".to_string()])
+            .build()
+            .unwrap();
+        assert_eq!(
+            options,
+            GenerationOptions {
+                beam_size: 4,
+                max_length: 16,
+                sampling_topk: 10,
+                end_token: vec!["</s>".to_string()],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn default_options_are_valid() {
+        assert!(GenerationOptions::default().validate().is_ok());
+        assert!(GenerationOptions::builder().build().is_ok());
+    }
+
+    #[test]
+    fn invalid_options_are_rejected() {
+        let builder = GenerationOptions::builder;
+        assert_eq!(
+            reason(builder().beam_size(0)),
+            "beam_size must be at least 1"
+        );
+        assert_eq!(
+            reason(builder().num_hypotheses(0)),
+            "num_hypotheses must be at least 1"
+        );
+        assert_eq!(
+            reason(builder().min_length(8).max_length(4)),
+            "min_length must not be greater than max_length"
+        );
+        assert_eq!(
+            reason(builder().sampling_topp(0.)),
+            "sampling_topp must be in (0, 1]"
+        );
+        assert_eq!(
+            reason(builder().sampling_topp(1.5)),
+            "sampling_topp must be in (0, 1]"
+        );
+        assert_eq!(
+            reason(builder().sampling_temperature(f32::NAN)),
+            "sampling_temperature must be positive"
+        );
+        assert_eq!(reason(builder().patience(0.)), "patience must be positive");
+        assert_eq!(
+            reason(builder().repetition_penalty(-1.)),
+            "repetition_penalty must be positive"
+        );
+        assert_eq!(
+            reason(builder().min_alternative_expansion_prob(2.)),
+            "min_alternative_expansion_prob must be in [0, 1]"
+        );
+    }
+
+    #[test]
+    fn check_reports_the_call() {
+        let options = GenerationOptions {
+            beam_size: 0,
+            ..Default::default()
+        };
+        let error = options.check(context()).unwrap_err();
+        assert_eq!(error.context().call, "test");
+    }
+
+    fn log_probs(ids: &[usize], tokens: &[f32]) -> HypothesisLogProbs {
+        let mut log_probs = HypothesisLogProbs::default();
+        log_probs.ids = ids.to_vec();
+        log_probs.tokens = tokens.to_vec();
+        log_probs
+    }
+
+    fn result(sequence_ids: Vec<Vec<usize>>, scores: Vec<f32>) -> ffi::GenerationResult {
+        let sequences = sequence_ids
+            .iter()
+            .map(|ids| ids.iter().map(|id| id.to_string()).collect())
+            .collect();
+        ffi::GenerationResult {
+            sequences: ffi::VecVecString::new_unique_from(sequences),
+            sequence_ids: ffi::VecVecUsize::new_unique_from(sequence_ids),
+            scores,
+        }
+    }
+
+    #[test]
+    fn log_probs_follow_re_sorted_hypotheses() {
+        let collected = vec![
+            log_probs(&[3, 4], &[-1., -2.]),
+            log_probs(&[5, 6], &[-0.1, -0.2]),
+        ];
+        let matched = match_log_probs(&[vec![5, 6], vec![3, 4]], collected);
+        assert_eq!(matched, vec![Some(vec![-0.1, -0.2]), Some(vec![-1., -2.])]);
+    }
+
+    #[test]
+    fn log_probs_skip_the_unreturned_end_token() {
+        let collected = vec![
+            log_probs(&[3, 2], &[-1., -0.5]),
+            log_probs(&[3, 4], &[-1., -2.]),
+        ];
+        let matched = match_log_probs(&[vec![3, 4], vec![3]], collected);
+        assert_eq!(matched, vec![Some(vec![-1., -2.]), Some(vec![-1.])]);
+    }
+
+    #[test]
+    fn log_probs_match_hypotheses_that_include_the_prompt() {
+        let collected = vec![log_probs(&[3, 4], &[-1., -2.])];
+        let matched = match_log_probs(&[vec![1, 7, 3, 4], vec![1, 7, 5]], collected);
+        assert_eq!(matched, vec![Some(vec![-1., -2.]), None]);
+    }
+
+    #[test]
+    fn from_ffi_converts_the_hypotheses() {
+        let result = GenerationResult::from_ffi(result(vec![vec![3, 4]], vec![-0.5]), 0, None);
+        assert_eq!(
+            result.hypotheses,
+            vec![Hypothesis {
+                tokens: vec!["3".to_string(), "4".to_string()],
+                ids: vec![3, 4],
+                score: Some(-0.5),
+                token_log_probs: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn from_ffi_batch_takes_the_log_probs_of_each_input() {
+        let collected = TokenLogProbs::default();
+        {
+            let mut collected = collected.lock().unwrap();
+            collected.insert((0, 0), log_probs(&[3, 2], &[-1., -0.5]));
+            collected.insert((1, 0), log_probs(&[5, 6], &[-0.1, -0.2]));
+            collected.insert((1, 1), log_probs(&[7, 8], &[-0.3, -0.4]));
+        }
+        let results = GenerationResult::from_ffi_batch(
+            vec![
+                result(vec![vec![3]], vec![]),
+                result(vec![vec![7, 8], vec![5, 6]], vec![-0.7, -0.3]),
+            ],
+            Some(&collected),
+        );
+        let token_log_probs: Vec<Vec<_>> = results
+            .into_iter()
+            .map(|result| {
+                result
+                    .into_iter()
+                    .map(|hypothesis| hypothesis.token_log_probs)
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            token_log_probs,
+            vec![
+                vec![Some(vec![-1.])],
+                vec![Some(vec![-0.3, -0.4]), Some(vec![-0.1, -0.2])],
+            ]
+        );
+        assert!(collected.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,781 @@
+use crate::batch_future::{GenerateBatchState, ResultTargets};
+use crate::callback::StepCallback;
+use crate::loader::{LoadOptions, LoadedModel};
+use crate::step_stream::{StepChannel, StepReceiver, StepSender};
+use crate::{
+    ffi, BatchType, CTranslate2Error, CancellationToken, ComputeType, Device, ErrorContext,
+    GenerateAsyncContext, GenerateBatchFuture, GenerateBatchStream, GenerateCallbackContext,
+    GenerationOptions, GenerationResult, GenerationStepIterator, GenerationStepResult,
+    GenerationStepStream, LoadSummary, ModelReader, ParseError, ScoringOptions, ScoringResult,
+    StorageView,
+};
+use cxx::UniquePtr;
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Merges the step callback with the cancellation checks, or returns `None` when there is
+// nothing to do on each step. The token log probabilities are collected by the step callbacks,
+// so one is also needed when they are requested. `stopped` is set when the cancellation token
+// stopped the decoding.
+fn step_callback<'a, F>(
+    callback: Option<F>,
+    cancellation: Option<&'a CancellationToken>,
+    stopped: &'a AtomicBool,
+    return_log_prob: bool,
+) -> Option<StepCallback<'a>>
+where
+    F: FnMut(GenerationStepResult) -> bool + Send + 'a,
+{
+    if callback.is_none() && cancellation.is_none() && !return_log_prob {
+        return None;
+    }
+    let mut callback = callback;
+    Some(Box::new(move |step| {
+        if cancellation.is_some_and(CancellationToken::should_stop) {
+            stopped.store(true, Ordering::SeqCst);
+            return true;
+        }
+        callback.as_mut().is_some_and(|callback| callback(step))
+    }))
+}
+
+// Checks the cancellation token before a batch is submitted. Beam search does not report the
+// decoding steps, so the token could not stop it once submitted.
+fn check_cancellation(
+    cancellation: Option<&CancellationToken>,
+    options: &GenerationOptions,
+    context: ErrorContext,
+) -> Result<(), CTranslate2Error> {
+    let Some(cancellation) = cancellation else {
+        return Ok(());
+    };
+    if options.beam_size > 1 {
+        return Err(CTranslate2Error::InvalidOptions {
+            context,
+            reason: "a cancellation token requires beam_size 1".to_string(),
+        });
+    }
+    cancellation.check(context)
+}
+
+// Groups the items that share the same options, keeping their order. The options are not
+// hashable (they contain floats), and there are usually only a few distinct ones, so the
+// groups are searched linearly.
+pub(crate) fn group_by_options<T>(
+    items: impl IntoIterator<Item = (GenerationOptions, T)>,
+) -> Vec<(GenerationOptions, Vec<T>)> {
+    let mut groups: Vec<(GenerationOptions, Vec<T>)> = Vec::new();
+    for (options, item) in items {
+        match groups.iter_mut().find(|(group, _)| *group == options) {
+            Some((_, group)) => group.push(item),
+            None => groups.push((options, vec![item])),
+        }
+    }
+    groups
+}
+
+// Prompt of `Generator::generate_steps`.
+enum StepPrompt {
+    Tokens(Vec<String>),
+    Ids(Vec<u32>),
+}
+
+pub struct Generator {
+    generator: UniquePtr<ffi::GeneratorWrapper>,
+    model_path: String,
+    load_summary: LoadSummary,
+}
+
+impl Generator {
+    /// Loads the model directory at `model_path`. With the `tar`, `zstd` or `zip` features,
+    /// `model_path` can also be an archive of that directory, see `ArchiveModelReader`.
+    pub fn new(
+        model_path: &str,
+        device: Device,
+        device_indicies: &[i32],
+        compute_type: ComputeType,
+        inter_threads: usize,
+        intra_threads: usize,
+        max_queued_batches: i32,
+    ) -> Result<Generator, CTranslate2Error> {
+        LoadOptions {
+            device,
+            device_indicies,
+            compute_type,
+            inter_threads,
+            intra_threads,
+            max_queued_batches,
+        }
+        .load_path("Generator::new", model_path)
+        .map(Generator::from_loaded)
+    }
+
+    /// Loads a model from in-memory files, keyed by their name in the model directory (e.g.
+    /// `model.bin`, `config.json` and the vocabulary files). CTranslate2 reads the buffers in
+    /// place, without copying them.
+    pub fn from_files(
+        files: HashMap<String, Vec<u8>>,
+        device: Device,
+        device_indicies: &[i32],
+        compute_type: ComputeType,
+        inter_threads: usize,
+        intra_threads: usize,
+        max_queued_batches: i32,
+    ) -> Result<Generator, CTranslate2Error> {
+        let files = files
+            .into_iter()
+            .map(|(name, content)| ffi::ModelFile { name, content })
+            .collect();
+        LoadOptions {
+            device,
+            device_indicies,
+            compute_type,
+            inter_threads,
+            intra_threads,
+            max_queued_batches,
+        }
+        .load(
+            "Generator::from_files",
+            "<memory>".to_string(),
+            files,
+            ffi::new_generator_wrapper_from_files,
+        )
+        .map(Generator::from_loaded)
+    }
+
+    /// Loads a model whose files are provided by a custom `ModelReader`.
+    pub fn from_reader<R: ModelReader + 'static>(
+        reader: R,
+        device: Device,
+        device_indicies: &[i32],
+        compute_type: ComputeType,
+        inter_threads: usize,
+        intra_threads: usize,
+        max_queued_batches: i32,
+    ) -> Result<Generator, CTranslate2Error> {
+        LoadOptions {
+            device,
+            device_indicies,
+            compute_type,
+            inter_threads,
+            intra_threads,
+            max_queued_batches,
+        }
+        .load_reader("Generator::from_reader", reader)
+        .map(Generator::from_loaded)
+    }
+
+    /// Same as `Generator::new`, but the model files are read through memory maps, see
+    /// `MmapModelReader`.
+    #[cfg(feature = "mmap")]
+    pub fn from_mmap(
+        model_path: &str,
+        device: Device,
+        device_indicies: &[i32],
+        compute_type: ComputeType,
+        inter_threads: usize,
+        intra_threads: usize,
+        max_queued_batches: i32,
+    ) -> Result<Generator, CTranslate2Error> {
+        LoadOptions {
+            device,
+            device_indicies,
+            compute_type,
+            inter_threads,
+            intra_threads,
+            max_queued_batches,
+        }
+        .load_mmap("Generator::from_mmap", model_path)
+        .map(Generator::from_loaded)
+    }
+
+    fn from_loaded(model: LoadedModel<ffi::GeneratorWrapper>) -> Generator {
+        Generator {
+            generator: model.wrapper,
+            model_path: model.model_path,
+            load_summary: model.load_summary,
+        }
+    }
+
+    /// Time and memory used to load the model, see `LoadSummary`.
+    pub fn load_summary(&self) -> &LoadSummary {
+        &self.load_summary
+    }
+
+    pub(crate) fn context(&self, call: &'static str) -> ErrorContext {
+        ErrorContext::new(call, &self.model_path)
+    }
+
+    pub fn device(&self) -> Result<Device, ParseError> {
+        self.generator.device().parse()
+    }
+
+    pub fn num_replicas(&self) -> usize {
+        self.generator.num_replicas()
+    }
+
+    pub fn num_queued_batches(&self) -> usize {
+        self.generator.num_queued_batches()
+    }
+
+    pub fn num_active_batches(&self) -> usize {
+        self.generator.num_active_batches()
+    }
+
+    /// Generates from a batch of prompts. When given, `callback` is called for each generated
+    /// token (greedy search and sampling only) and can borrow or mutate local state; returning
+    /// `true` stops the decoding of the batch.
+    ///
+    /// When `cancellation` is cancelled or its deadline passes, the decoding stops at the next
+    /// step and the call fails with `Cancelled`/`TimedOut`, without the partial hypotheses.
+    /// Since beam search does not report its steps, a `cancellation` token requires `beam_size` 1.
+    pub fn generate_batch<F>(
+        &self,
+        tokens: Vec<Vec<String>>,
+        max_batch_size: usize,
+        batch_type: BatchType,
+        options: GenerationOptions,
+        callback: Option<F>,
+        cancellation: Option<&CancellationToken>,
+    ) -> Result<Vec<GenerationResult>, CTranslate2Error>
+    where
+        F: FnMut(GenerationStepResult) -> bool + Send,
+    {
+        options.check(self.context("Generator::generate_batch"))?;
+        check_cancellation(
+            cancellation,
+            &options,
+            self.context("Generator::generate_batch"),
+        )?;
+        let stopped = AtomicBool::new(false);
+        let callback = step_callback(callback, cancellation, &stopped, options.return_log_prob);
+        match callback {
+            Some(callback) => {
+                // SAFETY: the call blocks until every job using the context is done, and the
+                // context is dropped right after.
+                let context = unsafe { GenerateCallbackContext::new_scoped(callback) };
+                let result = self
+                    .generator
+                    .generate_batch_with_callback(
+                        ffi::VecVecString::new_unique_from(tokens),
+                        max_batch_size,
+                        &batch_type.to_string(),
+                        Box::new(options.into_ffi()),
+                        GenerateCallbackContext::call,
+                        &context,
+                    )
+                    .map_err(|ex| {
+                        CTranslate2Error::from_exception(
+                            self.context("Generator::generate_batch"),
+                            ex,
+                        )
+                    })
+                    .map(|results| {
+                        GenerationResult::from_ffi_batch(results, Some(&context.log_probs))
+                    });
+                let results =
+                    context.into_result(self.context("Generator::generate_batch"), result)?;
+                // The token only stops the decoding once it is cancelled or expired, which it stays.
+                if let Some(cancellation) = cancellation.filter(|_| stopped.load(Ordering::SeqCst))
+                {
+                    cancellation.check(self.context("Generator::generate_batch"))?;
+                }
+                Ok(results)
+            }
+            None => self
+                .generator
+                .generate_batch(
+                    ffi::VecVecString::new_unique_from(tokens),
+                    max_batch_size,
+                    &batch_type.to_string(),
+                    Box::new(options.into_ffi()),
+                )
+                .map_err(|ex| {
+                    CTranslate2Error::from_exception(self.context("Generator::generate_batch"), ex)
+                })
+                .map(|results| GenerationResult::from_ffi_batch(results, None)),
+        }
+    }
+
+    /// Same as `generate_batch`, but returns immediately. The returned future is completed
+    /// from a CTranslate2 thread, so it can be awaited without `spawn_blocking`. Dropping the
+    /// future does not stop the generation, `cancellation` does as in `generate_batch`.
+    pub fn generate_batch_async(
+        &self,
+        tokens: Vec<Vec<String>>,
+        max_batch_size: usize,
+        batch_type: BatchType,
+        options: GenerationOptions,
+        cancellation: Option<&CancellationToken>,
+    ) -> GenerateBatchFuture {
+        let state = GenerateBatchState::new(tokens.len());
+        let context = self.context("Generator::generate_batch_async");
+        let checked = options
+            .check(context.clone())
+            .and_then(|_| check_cancellation(cancellation, &options, context.clone()));
+        if let Err(error) = checked {
+            return GenerateBatchFuture {
+                state,
+                error: Some(error),
+            };
+        }
+        let error = self
+            .submit_batch_async(
+                ffi::VecVecString::new_unique_from(tokens).pin_mut(),
+                max_batch_size,
+                batch_type,
+                options,
+                cancellation,
+                GenerateAsyncContext::new(ResultTargets::Batch(state.clone()), context),
+            )
+            .err();
+        GenerateBatchFuture { state, error }
+    }
+
+    /// Same as `generate_batch_async`, but yields each result as soon as its example is done,
+    /// together with the index of the example in `tokens`. Errors that prevent the batch from
+    /// being submitted are returned directly.
+    pub fn generate_batch_unordered(
+        &self,
+        tokens: Vec<Vec<String>>,
+        max_batch_size: usize,
+        batch_type: BatchType,
+        options: GenerationOptions,
+        cancellation: Option<&CancellationToken>,
+    ) -> Result<GenerateBatchStream, CTranslate2Error> {
+        let future =
+            self.generate_batch_async(tokens, max_batch_size, batch_type, options, cancellation);
+        match future.error {
+            Some(error) => Err(error),
+            None => Ok(GenerateBatchStream {
+                state: future.state,
+            }),
+        }
+    }
+
+    /// Generates from requests that each come with their own options, and returns the results
+    /// in the order of `requests`. Requests with equal options are generated together, in
+    /// batches of at most `max_batch_size`. `cancellation` stops all of them.
+    pub fn generate_requests_async(
+        &self,
+        requests: Vec<(Vec<String>, GenerationOptions)>,
+        max_batch_size: usize,
+        batch_type: BatchType,
+        cancellation: Option<&CancellationToken>,
+    ) -> GenerateBatchFuture {
+        let state = GenerateBatchState::new(requests.len());
+        let context = self.context("Generator::generate_requests_async");
+        for (_, options) in &requests {
+            let checked = options
+                .check(context.clone())
+                .and_then(|_| check_cancellation(cancellation, options, context.clone()));
+            if let Err(error) = checked {
+                return GenerateBatchFuture {
+                    state,
+                    error: Some(error),
+                };
+            }
+        }
+
+        let requests = requests
+            .into_iter()
+            .enumerate()
+            .map(|(index, (tokens, options))| (options, (tokens, (state.clone(), index))));
+        for (options, examples) in group_by_options(requests) {
+            let (tokens, targets) = examples.into_iter().unzip();
+            let result = self.submit_batch_async(
+                ffi::VecVecString::new_unique_from(tokens).pin_mut(),
+                max_batch_size,
+                batch_type,
+                options,
+                cancellation,
+                GenerateAsyncContext::new(ResultTargets::Examples(targets), context.clone()),
+            );
+            if let Err(error) = result {
+                return GenerateBatchFuture {
+                    state,
+                    error: Some(error),
+                };
+            }
+        }
+        GenerateBatchFuture { state, error: None }
+    }
+
+    // Submits the batch without waiting for it. The results are delivered to `async_context`.
+    // The tokens are taken from `tokens`, unless the batch could not be submitted.
+    pub(crate) fn submit_batch_async(
+        &self,
+        tokens: Pin<&mut ffi::VecVecString>,
+        max_batch_size: usize,
+        batch_type: BatchType,
+        options: GenerationOptions,
+        cancellation: Option<&CancellationToken>,
+        mut async_context: GenerateAsyncContext,
+    ) -> Result<(), CTranslate2Error> {
+        let context = async_context.context.clone();
+        let batch_type = batch_type.to_string();
+        if options.return_log_prob || cancellation.is_some() {
+            // The log probabilities are collected by a step callback, which also checks the
+            // cancellation token.
+            let cancellation = cancellation.cloned();
+            let stopped = async_context.stopped.clone();
+            async_context.cancellation = cancellation.clone();
+            let step_context = GenerateCallbackContext::new(move |_| {
+                let stop = cancellation
+                    .as_ref()
+                    .is_some_and(CancellationToken::should_stop);
+                if stop {
+                    stopped.store(true, Ordering::SeqCst);
+                }
+                stop
+            });
+            async_context.log_probs = Some(step_context.log_probs.clone());
+            async_context.step_panic = Some(step_context.panic.clone());
+            self.generator.generate_batch_async_with_callback(
+                tokens,
+                max_batch_size,
+                &batch_type,
+                Box::new(options.into_ffi()),
+                GenerateCallbackContext::call,
+                Box::new(step_context),
+                GenerateAsyncContext::on_ready,
+                Box::new(async_context),
+            )
+        } else {
+            self.generator.generate_batch_async(
+                tokens,
+                max_batch_size,
+                &batch_type,
+                Box::new(options.into_ffi()),
+                GenerateAsyncContext::on_ready,
+                Box::new(async_context),
+            )
+        }
+        .map_err(|ex| CTranslate2Error::from_exception(context, ex))
+    }
+
+    /// Generates from a single prompt and yields each token as soon as it is decoded.
+    /// Tokens can only be streamed with greedy search or sampling, so `beam_size` and
+    /// `num_hypotheses` are set to 1.
+    pub fn generate_tokens(
+        &self,
+        prompt: Vec<String>,
+        options: GenerationOptions,
+    ) -> GenerationStepIterator {
+        GenerationStepIterator(self.generate_steps(
+            StepPrompt::Tokens(prompt),
+            options,
+            "Generator::generate_tokens",
+        ))
+    }
+
+    /// Same as `generate_tokens`, but returns an asynchronous stream.
+    pub fn generate_tokens_stream(
+        &self,
+        prompt: Vec<String>,
+        options: GenerationOptions,
+    ) -> GenerationStepStream {
+        GenerationStepStream(self.generate_steps(
+            StepPrompt::Tokens(prompt),
+            options,
+            "Generator::generate_tokens_stream",
+        ))
+    }
+
+    /// Same as `generate_tokens`, but takes the token IDs of the prompt.
+    pub fn generate_tokens_ids(
+        &self,
+        prompt: Vec<u32>,
+        options: GenerationOptions,
+    ) -> GenerationStepIterator {
+        GenerationStepIterator(self.generate_steps(
+            StepPrompt::Ids(prompt),
+            options,
+            "Generator::generate_tokens_ids",
+        ))
+    }
+
+    /// Same as `generate_tokens_stream`, but takes the token IDs of the prompt.
+    pub fn generate_tokens_stream_ids(
+        &self,
+        prompt: Vec<u32>,
+        options: GenerationOptions,
+    ) -> GenerationStepStream {
+        GenerationStepStream(self.generate_steps(
+            StepPrompt::Ids(prompt),
+            options,
+            "Generator::generate_tokens_stream_ids",
+        ))
+    }
+
+    fn generate_steps(
+        &self,
+        prompt: StepPrompt,
+        mut options: GenerationOptions,
+        call: &'static str,
+    ) -> StepReceiver {
+        options.beam_size = 1;
+        options.num_hypotheses = 1;
+
+        let channel = StepChannel::new();
+        let sender = StepSender(channel.clone());
+        let state = GenerateBatchState::new(1);
+        if let Err(error) = options.check(self.context(call)) {
+            return StepReceiver::new(
+                channel,
+                GenerateBatchFuture {
+                    state,
+                    error: Some(error),
+                },
+            );
+        }
+        let batch_type = BatchType::Examples.to_string();
+        let options = Box::new(options.into_ffi());
+        let step_context = Box::new(GenerateCallbackContext::new(move |step| !sender.send(step)));
+        let mut async_context = Box::new(GenerateAsyncContext::new(
+            ResultTargets::Batch(state.clone()),
+            self.context(call),
+        ));
+        async_context.step_panic = Some(step_context.panic.clone());
+        let result = match prompt {
+            StepPrompt::Tokens(tokens) => self.generator.generate_batch_async_with_callback(
+                ffi::VecVecString::new_unique_from(vec![tokens]).pin_mut(),
+                0,
+                &batch_type,
+                options,
+                GenerateCallbackContext::call,
+                step_context,
+                GenerateAsyncContext::on_ready,
+                async_context,
+            ),
+            StepPrompt::Ids(ids) => self.generator.generate_batch_ids_async_with_callback(
+                ffi::VecVecUsize::new_unique_from_ids(&[ids]).pin_mut(),
+                0,
+                &batch_type,
+                options,
+                GenerateCallbackContext::call,
+                step_context,
+                GenerateAsyncContext::on_ready,
+                async_context,
+            ),
+        };
+        let error = result
+            .err()
+            .map(|ex| CTranslate2Error::from_exception(self.context(call), ex));
+        StepReceiver::new(channel, GenerateBatchFuture { state, error })
+    }
+
+    /// Same as `generate_batch`, but takes the token IDs (e.g. as produced by a HuggingFace
+    /// tokenizer) instead of the token strings.
+    pub fn generate_batch_ids<F>(
+        &self,
+        ids: &[Vec<u32>],
+        max_batch_size: usize,
+        batch_type: BatchType,
+        options: GenerationOptions,
+        callback: Option<F>,
+        cancellation: Option<&CancellationToken>,
+    ) -> Result<Vec<GenerationResult>, CTranslate2Error>
+    where
+        F: FnMut(GenerationStepResult) -> bool + Send,
+    {
+        options.check(self.context("Generator::generate_batch_ids"))?;
+        check_cancellation(
+            cancellation,
+            &options,
+            self.context("Generator::generate_batch_ids"),
+        )?;
+        let stopped = AtomicBool::new(false);
+        let callback = step_callback(callback, cancellation, &stopped, options.return_log_prob);
+        match callback {
+            Some(callback) => {
+                // SAFETY: the call blocks until every job using the context is done, and the
+                // context is dropped right after.
+                let context = unsafe { GenerateCallbackContext::new_scoped(callback) };
+                let result = self
+                    .generator
+                    .generate_batch_ids_with_callback(
+                        ffi::VecVecUsize::new_unique_from_ids(ids),
+                        max_batch_size,
+                        &batch_type.to_string(),
+                        Box::new(options.into_ffi()),
+                        GenerateCallbackContext::call,
+                        &context,
+                    )
+                    .map_err(|ex| {
+                        CTranslate2Error::from_exception(
+                            self.context("Generator::generate_batch_ids"),
+                            ex,
+                        )
+                    })
+                    .map(|results| {
+                        GenerationResult::from_ffi_batch(results, Some(&context.log_probs))
+                    });
+                let results =
+                    context.into_result(self.context("Generator::generate_batch_ids"), result)?;
+                // The token only stops the decoding once it is cancelled or expired, which it stays.
+                if let Some(cancellation) = cancellation.filter(|_| stopped.load(Ordering::SeqCst))
+                {
+                    cancellation.check(self.context("Generator::generate_batch_ids"))?;
+                }
+                Ok(results)
+            }
+            None => self
+                .generator
+                .generate_batch_ids(
+                    ffi::VecVecUsize::new_unique_from_ids(ids),
+                    max_batch_size,
+                    &batch_type.to_string(),
+                    Box::new(options.into_ffi()),
+                )
+                .map_err(|ex| {
+                    CTranslate2Error::from_exception(
+                        self.context("Generator::generate_batch_ids"),
+                        ex,
+                    )
+                })
+                .map(|results| GenerationResult::from_ffi_batch(results, None)),
+        }
+    }
+
+    /// Runs a forward pass on the full sequences and returns the logits (or log probabilities)
+    /// with shape `[batch_size, max_length, vocabulary_size]`.
+    pub fn forward_batch(
+        &self,
+        tokens: Vec<Vec<String>>,
+        return_log_probs: bool,
+    ) -> Result<StorageView, CTranslate2Error> {
+        let view = self
+            .generator
+            .forward_batch(ffi::VecVecString::new_unique_from(tokens), return_log_probs)
+            .map_err(|ex| {
+                CTranslate2Error::from_exception(self.context("Generator::forward_batch"), ex)
+            })?;
+        Ok(StorageView { view })
+    }
+
+    pub fn score_batch(
+        &self,
+        tokens: Vec<Vec<String>>,
+        max_batch_size: usize,
+        batch_type: BatchType,
+        options: ScoringOptions,
+    ) -> Result<Vec<ScoringResult>, CTranslate2Error> {
+        self.generator
+            .score_batch(
+                ffi::VecVecString::new_unique_from(tokens),
+                max_batch_size,
+                &batch_type.to_string(),
+                Box::new(options),
+            )
+            .map_err(|ex| {
+                CTranslate2Error::from_exception(self.context("Generator::score_batch"), ex)
+            })
+    }
+
+    /// Same as `score_batch`, but takes the token IDs instead of the token strings.
+    pub fn score_batch_ids(
+        &self,
+        ids: &[Vec<u32>],
+        max_batch_size: usize,
+        batch_type: BatchType,
+        options: ScoringOptions,
+    ) -> Result<Vec<ScoringResult>, CTranslate2Error> {
+        self.generator
+            .score_batch_ids(
+                ffi::VecVecUsize::new_unique_from_ids(ids),
+                max_batch_size,
+                &batch_type.to_string(),
+                Box::new(options),
+            )
+            .map_err(|ex| {
+                CTranslate2Error::from_exception(self.context("Generator::score_batch_ids"), ex)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{context, step};
+
+    #[test]
+    fn group_by_options_keeps_the_order() {
+        let sampling = GenerationOptions {
+            sampling_topk: 10,
+            ..Default::default()
+        };
+        let groups = group_by_options([
+            (GenerationOptions::default(), 0),
+            (sampling.clone(), 1),
+            (GenerationOptions::default(), 2),
+            (sampling.clone(), 3),
+        ]);
+        assert_eq!(
+            groups,
+            vec![
+                (GenerationOptions::default(), vec![0, 2]),
+                (sampling, vec![1, 3])
+            ]
+        );
+        assert!(group_by_options(Vec::<(GenerationOptions, usize)>::new()).is_empty());
+    }
+
+    #[test]
+    fn cancellation_rejects_beam_search() {
+        let cancellation = CancellationToken::new();
+        let options = GenerationOptions::builder().beam_size(4).build().unwrap();
+        assert!(matches!(
+            check_cancellation(Some(&cancellation), &options, context()),
+            Err(CTranslate2Error::InvalidOptions { .. })
+        ));
+        assert!(check_cancellation(None, &options, context()).is_ok());
+        assert!(check_cancellation(Some(&cancellation), &Default::default(), context()).is_ok());
+    }
+
+    #[test]
+    fn cancellation_is_checked_before_submitting() {
+        let cancellation = CancellationToken::new();
+        cancellation.cancel();
+        assert!(matches!(
+            check_cancellation(Some(&cancellation), &Default::default(), context()),
+            Err(CTranslate2Error::Cancelled { .. })
+        ));
+    }
+
+    #[test]
+    fn cancellation_stops_the_decoding() {
+        let cancellation = CancellationToken::new();
+        let stopped = AtomicBool::new(false);
+        let mut steps = 0;
+        let mut callback = step_callback(
+            Some(|_| {
+                steps += 1;
+                false
+            }),
+            Some(&cancellation),
+            &stopped,
+            false,
+        )
+        .unwrap();
+        assert!(!callback(step()));
+        cancellation.cancel();
+        assert!(callback(step()));
+        drop(callback);
+        assert!(stopped.into_inner());
+        assert_eq!(steps, 1);
+    }
+
+    #[test]
+    fn no_step_callback_without_callback_cancellation_or_log_probs() {
+        let stopped = AtomicBool::new(false);
+        let callback = step_callback(
+            None::<fn(GenerationStepResult) -> bool>,
+            None,
+            &stopped,
+            false,
+        );
+        assert!(callback.is_none());
+    }
+}
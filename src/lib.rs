@@ -1,18 +1,84 @@
 #[allow(unused_imports)]
 #[allow(dead_code)]
 use cxx::UniquePtr;
-use std::str::FromStr;
+
+mod batch_future;
+mod batching;
+mod callback;
+mod cancellation;
+mod encoder;
+mod error;
+mod generation;
+mod generator;
+mod loader;
+mod model_reader;
+mod step_stream;
+mod storage_view;
+#[cfg(test)]
+mod testing;
+mod translator;
+mod types;
+mod whisper;
+
+pub mod model_spec;
+
+#[cfg(any(feature = "tar", feature = "zip"))]
+mod archive;
+#[cfg(any(feature = "tar", feature = "zip"))]
+pub use archive::{pack_model, ArchiveModelReader};
+#[cfg(feature = "encryption")]
+mod encryption;
+#[cfg(feature = "encryption")]
+pub use encryption::{encrypt_model, generate_key, EncryptedModelReader};
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "mmap")]
+pub use mmap::MmapModelReader;
+
+pub use batch_future::{GenerateAsyncContext, GenerateBatchFuture, GenerateBatchStream};
+pub use batching::{BatchingGenerator, GenerateFuture};
+pub use callback::GenerateCallbackContext;
+pub use cancellation::CancellationToken;
+pub use encoder::{Encoder, EncoderForwardOutput};
+pub use error::{CTranslate2Error, ErrorContext, ParseError};
+pub use generation::{
+    GenerationOptions, GenerationOptionsBuilder, GenerationResult, GenerationStepResult, Hypothesis,
+};
+pub use generator::Generator;
+pub use model_reader::{LoadSummary, ModelFileReader, ModelReader, ModelReaderContext};
+pub use step_stream::{GenerationStepIterator, GenerationStepStream};
+pub use storage_view::StorageView;
+pub use translator::{AttentionMatrix, TranslationOptions, TranslationResult, Translator};
+pub use types::{BatchType, ComputeType, DataType, Device};
+pub use whisper::{Whisper, WhisperAlignmentResult, WhisperGenerationResult, WhisperOptions};
 
 #[cxx::bridge]
+#[allow(clippy::too_many_arguments, clippy::len_without_is_empty)]
 pub mod ffi {
     extern "Rust" {
         type GenerateCallbackContext;
+        type GenerateAsyncContext;
+        fn discard(self: &GenerateAsyncContext);
+        type ModelReaderContext;
+        type ModelFileReader;
+        fn model_id(self: &ModelReaderContext) -> String;
+        fn open_file(self: &ModelReaderContext, filename: &str) -> Result<Box<ModelFileReader>>;
+        fn found(self: &ModelFileReader) -> bool;
+        fn read(self: &mut ModelFileReader, buf: &mut [u8]) -> Result<usize>;
+    }
+
+    struct ModelFile {
+        name: String,
+        content: Vec<u8>,
     }
 
     struct GenerationStepResult {
         step: usize,
         batch_id: usize,
+        input_index: usize,
+        hypothesis_id: usize,
         token_id: usize,
+        token: String,
         log_prob: f32,
         log_prob_valid: bool,
         is_last: bool,
@@ -49,6 +115,8 @@ pub mod ffi {
         empty_end_token_means_stop_on_eos_token: bool,
         // Include the end token in the result.
         return_end_token: bool,
+        // Include the log probability of each token in the step results.
+        return_log_prob: bool,
         // Max length constraint
         max_length: usize,
         // Min length constraint
@@ -77,9 +145,153 @@ pub mod ffi {
         include_prompt_in_result: bool,
     }
 
+    struct ScoringResult {
+        tokens: Vec<String>,
+        token_scores: Vec<f32>,
+    }
+
+    struct ScoringOptions {
+        // Truncate the inputs after this many tokens (set 0 to disable truncation).
+        max_input_length: usize,
+        // Ignore the first tokens of each input when computing the score.
+        offset: usize,
+    }
+
+    struct EncoderForwardOutput {
+        last_hidden_state: UniquePtr<StorageViewWrapper>,
+        pooler_output: UniquePtr<StorageViewWrapper>,
+    }
+
+    struct WhisperGenerationResult {
+        sequences: UniquePtr<VecVecString>,
+        sequence_ids: UniquePtr<VecVecUsize>,
+        scores: Vec<f32>,
+        no_speech_prob: f32,
+    }
+
+    struct WhisperLanguageDetection {
+        languages: Vec<String>,
+        probabilities: Vec<f32>,
+    }
+
+    struct WhisperAlignmentResult {
+        text_indices: Vec<usize>,
+        time_indices: Vec<usize>,
+        text_token_probs: Vec<f32>,
+    }
+
+    struct WhisperOptions {
+        // Beam size to use for beam search (set 1 to run greedy search).
+        beam_size: usize,
+        // Beam search patience factor, as described in https://arxiv.org/abs/2204.05424.
+        // The decoding will continue until beam_size*patience hypotheses are finished.
+        patience: f32,
+        // Exponential penalty applied to the length during beam search.
+        length_penalty: f32,
+        // Penalty applied to the score of previously generated tokens, as described in
+        // https://arxiv.org/abs/1909.05858 (set > 1 to penalize).
+        repetition_penalty: f32,
+        // Prevent repetitions of ngrams with this size (set 0 to disable).
+        no_repeat_ngram_size: usize,
+        // Maximum generation length.
+        max_length: usize,
+        // Randomly sample from the top K candidates (set 0 to sample from the full distribution).
+        sampling_topk: usize,
+        // High temperatures increase randomness.
+        sampling_temperature: f32,
+        // Number of hypotheses to include in the result.
+        num_hypotheses: usize,
+        // Include scores in the result.
+        return_scores: bool,
+        // Include the probability of the no speech token in the result.
+        return_no_speech_prob: bool,
+        // Maximum index of the first predicted timestamp.
+        max_initial_timestamp_index: usize,
+        // Suppress blank outputs at the beginning of the sampling.
+        suppress_blank: bool,
+        // List of token IDs to suppress.
+        // -1 will suppress a default set of symbols as defined in the model config.json file.
+        suppress_tokens: Vec<i32>,
+    }
+
+    struct AttentionMatrix {
+        rows: UniquePtr<VecVecF32>,
+    }
+
+    struct TranslationResult {
+        hypotheses: UniquePtr<VecVecString>,
+        scores: Vec<f32>,
+        attention: Vec<AttentionMatrix>,
+    }
+
+    struct TranslationOptions {
+        // Beam size to use for beam search (set 1 to run greedy search).
+        beam_size: usize,
+        // Beam search patience factor, as described in https://arxiv.org/abs/2204.05424.
+        // The decoding will continue until beam_size*patience hypotheses are finished.
+        patience: f32,
+        // Exponential penalty applied to the length during beam search.
+        // The scores are normalized with:
+        //   hypothesis_score /= (hypothesis_length ** length_penalty)
+        length_penalty: f32,
+        // Coverage penalty weight applied during beam search.
+        coverage_penalty: f32,
+        // Penalty applied to the score of previously generated tokens, as described in
+        // https://arxiv.org/abs/1909.05858 (set > 1 to penalize).
+        repetition_penalty: f32,
+        // Prevent repetitions of ngrams with this size (set 0 to disable).
+        no_repeat_ngram_size: usize,
+        // Disable the generation of the unknown token.
+        disable_unk: bool,
+        // Disable the generation of some sequences of tokens.
+        suppress_sequences: UniquePtr<VecVecString>,
+        // Biases decoding towards a given prefix, see https://arxiv.org/abs/1912.03393 --section 4.2
+        // Only activates biased-decoding when beta is in range (0, 1) and SearchStrategy is set to BeamSearch.
+        // The closer beta is to 1, the stronger the bias is towards the given prefix.
+        //
+        // If beta <= 0 and a non-empty prefix is given, then the prefix will be used as a
+        // hard-prefix rather than a soft, biased-prefix.
+        prefix_bias_beta: f32,
+        // Stop the decoding on one of these tokens
+        end_token: Vec<String>,
+        // If end_token is empty, top on the EOS token
+        empty_end_token_means_stop_on_eos_token: bool,
+        // Include the end token in the result.
+        return_end_token: bool,
+        // Truncate the inputs after this many tokens (set 0 to disable truncation).
+        max_input_length: usize,
+        // Decoding length constraints.
+        max_decoding_length: usize,
+        min_decoding_length: usize,
+        // Randomly sample from the top K candidates (set 0 to sample from the full output distribution).
+        sampling_topk: usize,
+        // Keep the most probable tokens whose cumulative probability exceeds this value.
+        sampling_topp: f32,
+        // High temperature increase randomness.
+        sampling_temperature: f32,
+        // Allow using the vocabulary map included in the model directory, if it exists.
+        use_vmap: bool,
+        // Number of hypotheses to include in the result.
+        num_hypotheses: usize,
+        // Include scores in the result.
+        return_scores: bool,
+        // Include the attention vectors of the last decoder layer in the result.
+        return_attention: bool,
+        // Return alternatives at the first unconstrained decoding position. This is typically
+        // used with a target prefix to provide alternatives at a specifc location in the
+        // translation.
+        return_alternatives: bool,
+        // Minimum probability to expand an alternative.
+        min_alternative_expansion_prob: f32,
+        // Replace unknown target tokens by the original source token with the highest attention.
+        replace_unknowns: bool,
+    }
+
     unsafe extern "C++" {
         include!("ctranslate2-rs/include/ctranslate2.h");
 
+        fn last_exception_kind() -> String;
+
         type VecVecString;
         fn at(self: &VecVecString, index: usize) -> Result<Vec<String>>;
         fn push_back(self: Pin<&mut VecVecString>, data: Vec<String>);
@@ -98,6 +310,28 @@ pub mod ffi {
         fn len(self: &VecVecUsize) -> usize;
         fn new_vec_vec_usize() -> UniquePtr<VecVecUsize>;
 
+        type VecVecF32;
+        fn at(self: &VecVecF32, index: usize) -> Result<Vec<f32>>;
+        fn push_back(self: Pin<&mut VecVecF32>, data: Vec<f32>);
+        fn clear(self: Pin<&mut VecVecF32>);
+        fn reserve(self: Pin<&mut VecVecF32>, size: usize);
+        fn empty(self: &VecVecF32) -> bool;
+        fn len(self: &VecVecF32) -> usize;
+        fn new_vec_vec_f32() -> UniquePtr<VecVecF32>;
+
+        type StorageViewWrapper;
+        fn shape(self: &StorageViewWrapper) -> Vec<usize>;
+        fn dtype(self: &StorageViewWrapper) -> String;
+        fn device(self: &StorageViewWrapper) -> String;
+        fn to_vec_f32(self: &StorageViewWrapper) -> Result<Vec<f32>>;
+        fn new_storage_view(
+            shape: &[usize],
+            data: Vec<f32>,
+        ) -> Result<UniquePtr<StorageViewWrapper>>;
+
+        type GenerationAsyncResult;
+        fn result(self: Pin<&mut GenerationAsyncResult>) -> Result<GenerationResult>;
+
         type GeneratorWrapper;
         fn device(self: &GeneratorWrapper) -> String;
         fn num_replicas(self: &GeneratorWrapper) -> usize;
@@ -117,8 +351,92 @@ pub mod ffi {
             batch_type_str: &str,
             options: Box<GenerationOptions>,
             callback: fn(result: GenerationStepResult, context: &GenerateCallbackContext) -> bool,
-            context: Box<GenerateCallbackContext>,
+            context: &GenerateCallbackContext,
+        ) -> Result<Vec<GenerationResult>>;
+        fn generate_batch_ids(
+            self: &GeneratorWrapper,
+            ids: UniquePtr<VecVecUsize>,
+            max_batch_size: usize,
+            batch_type_str: &str,
+            options: Box<GenerationOptions>,
         ) -> Result<Vec<GenerationResult>>;
+        fn generate_batch_ids_with_callback(
+            self: &GeneratorWrapper,
+            ids: UniquePtr<VecVecUsize>,
+            max_batch_size: usize,
+            batch_type_str: &str,
+            options: Box<GenerationOptions>,
+            callback: fn(result: GenerationStepResult, context: &GenerateCallbackContext) -> bool,
+            context: &GenerateCallbackContext,
+        ) -> Result<Vec<GenerationResult>>;
+        fn generate_batch_async(
+            self: &GeneratorWrapper,
+            tokens: Pin<&mut VecVecString>,
+            max_batch_size: usize,
+            batch_type_str: &str,
+            options: Box<GenerationOptions>,
+            callback: fn(
+                index: usize,
+                result: UniquePtr<GenerationAsyncResult>,
+                context: &GenerateAsyncContext,
+            ),
+            context: Box<GenerateAsyncContext>,
+        ) -> Result<()>;
+        fn generate_batch_async_with_callback(
+            self: &GeneratorWrapper,
+            tokens: Pin<&mut VecVecString>,
+            max_batch_size: usize,
+            batch_type_str: &str,
+            options: Box<GenerationOptions>,
+            step_callback: fn(
+                result: GenerationStepResult,
+                context: &GenerateCallbackContext,
+            ) -> bool,
+            step_context: Box<GenerateCallbackContext>,
+            callback: fn(
+                index: usize,
+                result: UniquePtr<GenerationAsyncResult>,
+                context: &GenerateAsyncContext,
+            ),
+            context: Box<GenerateAsyncContext>,
+        ) -> Result<()>;
+        fn generate_batch_ids_async_with_callback(
+            self: &GeneratorWrapper,
+            ids: Pin<&mut VecVecUsize>,
+            max_batch_size: usize,
+            batch_type_str: &str,
+            options: Box<GenerationOptions>,
+            step_callback: fn(
+                result: GenerationStepResult,
+                context: &GenerateCallbackContext,
+            ) -> bool,
+            step_context: Box<GenerateCallbackContext>,
+            callback: fn(
+                index: usize,
+                result: UniquePtr<GenerationAsyncResult>,
+                context: &GenerateAsyncContext,
+            ),
+            context: Box<GenerateAsyncContext>,
+        ) -> Result<()>;
+        fn score_batch(
+            self: &GeneratorWrapper,
+            tokens: UniquePtr<VecVecString>,
+            max_batch_size: usize,
+            batch_type_str: &str,
+            options: Box<ScoringOptions>,
+        ) -> Result<Vec<ScoringResult>>;
+        fn score_batch_ids(
+            self: &GeneratorWrapper,
+            ids: UniquePtr<VecVecUsize>,
+            max_batch_size: usize,
+            batch_type_str: &str,
+            options: Box<ScoringOptions>,
+        ) -> Result<Vec<ScoringResult>>;
+        fn forward_batch(
+            self: &GeneratorWrapper,
+            tokens: UniquePtr<VecVecString>,
+            return_log_probs: bool,
+        ) -> Result<UniquePtr<StorageViewWrapper>>;
         fn new_generator_wrapper(
             model_path: &str,
             device: &str,
@@ -128,154 +446,228 @@ pub mod ffi {
             intra_threads: usize,
             max_queued_batches: i32,
         ) -> Result<UniquePtr<GeneratorWrapper>>;
+        fn new_generator_wrapper_from_files(
+            files: Vec<ModelFile>,
+            device: &str,
+            device_indicies: Vec<i32>,
+            compute_type: &str,
+            inter_threads: usize,
+            intra_threads: usize,
+            max_queued_batches: i32,
+        ) -> Result<UniquePtr<GeneratorWrapper>>;
+        fn new_generator_wrapper_from_reader(
+            reader: Box<ModelReaderContext>,
+            device: &str,
+            device_indicies: Vec<i32>,
+            compute_type: &str,
+            inter_threads: usize,
+            intra_threads: usize,
+            max_queued_batches: i32,
+        ) -> Result<UniquePtr<GeneratorWrapper>>;
+
+        type TranslatorWrapper;
+        fn device(self: &TranslatorWrapper) -> String;
+        fn num_replicas(self: &TranslatorWrapper) -> usize;
+        fn num_queued_batches(self: &TranslatorWrapper) -> usize;
+        fn num_active_batches(self: &TranslatorWrapper) -> usize;
+        fn translate_batch(
+            self: &TranslatorWrapper,
+            source: UniquePtr<VecVecString>,
+            target_prefix: UniquePtr<VecVecString>,
+            max_batch_size: usize,
+            batch_type_str: &str,
+            options: Box<TranslationOptions>,
+        ) -> Result<Vec<TranslationResult>>;
+        fn score_batch(
+            self: &TranslatorWrapper,
+            source: UniquePtr<VecVecString>,
+            target: UniquePtr<VecVecString>,
+            max_batch_size: usize,
+            batch_type_str: &str,
+            options: Box<ScoringOptions>,
+        ) -> Result<Vec<ScoringResult>>;
+        fn new_translator_wrapper(
+            model_path: &str,
+            device: &str,
+            device_indicies: Vec<i32>,
+            compute_type: &str,
+            inter_threads: usize,
+            intra_threads: usize,
+            max_queued_batches: i32,
+        ) -> Result<UniquePtr<TranslatorWrapper>>;
+        fn new_translator_wrapper_from_reader(
+            reader: Box<ModelReaderContext>,
+            device: &str,
+            device_indicies: Vec<i32>,
+            compute_type: &str,
+            inter_threads: usize,
+            intra_threads: usize,
+            max_queued_batches: i32,
+        ) -> Result<UniquePtr<TranslatorWrapper>>;
+
+        type EncoderWrapper;
+        fn device(self: &EncoderWrapper) -> String;
+        fn num_replicas(self: &EncoderWrapper) -> usize;
+        fn num_queued_batches(self: &EncoderWrapper) -> usize;
+        fn num_active_batches(self: &EncoderWrapper) -> usize;
+        fn forward_batch(
+            self: &EncoderWrapper,
+            tokens: UniquePtr<VecVecString>,
+            token_type_ids: UniquePtr<VecVecUsize>,
+        ) -> Result<EncoderForwardOutput>;
+        fn new_encoder_wrapper(
+            model_path: &str,
+            device: &str,
+            device_indicies: Vec<i32>,
+            compute_type: &str,
+            inter_threads: usize,
+            intra_threads: usize,
+            max_queued_batches: i32,
+        ) -> Result<UniquePtr<EncoderWrapper>>;
+        fn new_encoder_wrapper_from_reader(
+            reader: Box<ModelReaderContext>,
+            device: &str,
+            device_indicies: Vec<i32>,
+            compute_type: &str,
+            inter_threads: usize,
+            intra_threads: usize,
+            max_queued_batches: i32,
+        ) -> Result<UniquePtr<EncoderWrapper>>;
+
+        type WhisperWrapper;
+        fn device(self: &WhisperWrapper) -> String;
+        fn num_replicas(self: &WhisperWrapper) -> usize;
+        fn num_queued_batches(self: &WhisperWrapper) -> usize;
+        fn num_active_batches(self: &WhisperWrapper) -> usize;
+        fn is_multilingual(self: &WhisperWrapper) -> bool;
+        fn generate(
+            self: &WhisperWrapper,
+            features: &StorageViewWrapper,
+            prompts: UniquePtr<VecVecString>,
+            options: Box<WhisperOptions>,
+        ) -> Result<Vec<WhisperGenerationResult>>;
+        fn detect_language(
+            self: &WhisperWrapper,
+            features: &StorageViewWrapper,
+        ) -> Result<Vec<WhisperLanguageDetection>>;
+        fn align(
+            self: &WhisperWrapper,
+            features: &StorageViewWrapper,
+            start_sequence: &[usize],
+            text_tokens: UniquePtr<VecVecUsize>,
+            num_frames: usize,
+            median_filter_width: usize,
+        ) -> Result<Vec<WhisperAlignmentResult>>;
+        fn new_whisper_wrapper(
+            model_path: &str,
+            device: &str,
+            device_indicies: Vec<i32>,
+            compute_type: &str,
+            inter_threads: usize,
+            intra_threads: usize,
+            max_queued_batches: i32,
+        ) -> Result<UniquePtr<WhisperWrapper>>;
+        fn new_whisper_wrapper_from_reader(
+            reader: Box<ModelReaderContext>,
+            device: &str,
+            device_indicies: Vec<i32>,
+            compute_type: &str,
+            inter_threads: usize,
+            intra_threads: usize,
+            max_queued_batches: i32,
+        ) -> Result<UniquePtr<WhisperWrapper>>;
     }
 }
 
-pub use ffi::{GenerationOptions, GenerationResult, GenerationStepResult};
+pub use ffi::{ScoringOptions, ScoringResult};
 
 unsafe impl Sync for ffi::GeneratorWrapper {}
+unsafe impl Sync for ffi::TranslatorWrapper {}
+unsafe impl Sync for ffi::EncoderWrapper {}
+unsafe impl Sync for ffi::WhisperWrapper {}
 unsafe impl Send for ffi::VecVecString {}
 unsafe impl Send for ffi::VecVecUsize {}
+unsafe impl Send for ffi::VecVecF32 {}
+unsafe impl Sync for ffi::StorageViewWrapper {}
+unsafe impl Send for ffi::StorageViewWrapper {}
 unsafe impl Send for ffi::GeneratorWrapper {}
-
-#[derive(Debug)]
-pub struct CTranslate2Error(cxx::Exception);
+unsafe impl Send for ffi::TranslatorWrapper {}
+unsafe impl Send for ffi::EncoderWrapper {}
+unsafe impl Send for ffi::WhisperWrapper {}
 
 pub fn set_cuda_allocator_to_cub_caching() {
     std::env::set_var("CT2_CUDA_ALLOCATOR", "cub_caching");
 }
 
-impl Default for GenerationOptions {
-    fn default() -> GenerationOptions {
-        GenerationOptions {
-            beam_size: 1,
-            patience: 1.,
-            length_penalty: 1.,
-            repetition_penalty: 1.,
-            no_repeat_ngram_size: 0,
-            disable_unk: false,
-            suppress_sequences: ffi::new_vec_vec_string(),
-            end_token: Vec::new(),
-            empty_end_token_means_stop_on_eos_token: true,
-            return_end_token: false,
-            max_length: 512,
-            min_length: 0,
-            sampling_topk: 1,
-            sampling_topp: 1.,
-            sampling_temperature: 1.,
-            num_hypotheses: 1,
-            return_scores: false,
-            return_alternatives: false,
-            min_alternative_expansion_prob: 0.,
-            static_prompt: Vec::new(),
-            cache_static_prompt: true,
-            include_prompt_in_result: true,
+impl Default for ScoringOptions {
+    fn default() -> ScoringOptions {
+        ScoringOptions {
+            max_input_length: 1024,
+            offset: 0,
         }
     }
 }
 
-#[derive(Debug)]
-pub struct ParseError(String);
-
-#[derive(Copy, Clone, Debug)]
-pub enum Device {
-    CPU,
-    CUDA,
-}
-
-impl ToString for Device {
-    fn to_string(&self) -> String {
-        match self {
-            Device::CPU => "cpu",
-            Device::CUDA => "cuda",
-        }
-        .to_string()
+impl ScoringResult {
+    /// Sum of the token scores.
+    pub fn cumulated_score(&self) -> f32 {
+        self.token_scores.iter().sum()
     }
-}
 
-impl FromStr for Device {
-    type Err = ParseError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_lowercase().as_str() {
-            "cpu" => Ok(Device::CPU),
-            "cuda" => Ok(Device::CUDA),
-            _ => Err(ParseError(format!("Unknown device {s}"))),
+    /// Average of the token scores.
+    pub fn normalized_score(&self) -> f32 {
+        if self.token_scores.is_empty() {
+            0.
+        } else {
+            self.cumulated_score() / self.token_scores.len() as f32
         }
     }
 }
 
-#[derive(Copy, Clone, Debug)]
-pub enum ComputeType {
-    Default,
-    Auto,
-    Float32,
-    Int8,
-    Int8Float16,
-    Int16,
-    Float16,
-}
-
-impl ToString for ComputeType {
-    fn to_string(&self) -> String {
-        match self {
-            ComputeType::Default => "default",
-            ComputeType::Auto => "auto",
-            ComputeType::Float32 => "float32",
-            ComputeType::Int8 => "int8",
-            ComputeType::Int8Float16 => "int8_float16",
-            ComputeType::Int16 => "int16",
-            ComputeType::Float16 => "float16",
+impl ffi::VecVecUsize {
+    pub fn new_unique_from(value: Vec<Vec<usize>>) -> UniquePtr<ffi::VecVecUsize> {
+        let mut v = ffi::new_vec_vec_usize();
+        v.pin_mut().reserve(value.len());
+        for item in value.into_iter() {
+            v.pin_mut().push_back(item);
         }
-        .to_string()
+        v
     }
-}
 
-impl FromStr for ComputeType {
-    type Err = ParseError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_lowercase().as_str() {
-            "default" => Ok(ComputeType::Default),
-            "auto" => Ok(ComputeType::Auto),
-            "float32" | "float" => Ok(ComputeType::Float32),
-            "int8" => Ok(ComputeType::Int8),
-            "int8_float16" => Ok(ComputeType::Int8Float16),
-            "float16" => Ok(ComputeType::Float16),
-            _ => Err(ParseError(format!("Unknown compute type {s}"))),
+    pub fn new_unique_from_ids(value: &[Vec<u32>]) -> UniquePtr<ffi::VecVecUsize> {
+        let mut v = ffi::new_vec_vec_usize();
+        v.pin_mut().reserve(value.len());
+        for item in value.iter() {
+            v.pin_mut()
+                .push_back(item.iter().map(|id| *id as usize).collect());
         }
+        v
     }
 }
 
-#[derive(Copy, Clone, Debug)]
-pub enum BatchType {
-    Examples,
-    Tokens,
-}
-
-impl ToString for BatchType {
-    fn to_string(&self) -> String {
-        match self {
-            BatchType::Examples => "examples",
-            BatchType::Tokens => "tokens",
-        }
-        .to_string()
+impl ffi::VecVecUsize {
+    pub fn to_vec(&self) -> Vec<Vec<usize>> {
+        (0..self.len())
+            .map(|index| self.at(index).expect("index is in range"))
+            .collect()
     }
 }
 
-impl FromStr for BatchType {
-    type Err = ParseError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_lowercase().as_str() {
-            "examples" => Ok(BatchType::Examples),
-            "tokens" => Ok(BatchType::Tokens),
-            _ => Err(ParseError(format!("Unknown batch type {s}"))),
-        }
+impl ffi::VecVecF32 {
+    pub fn to_vec(&self) -> Vec<Vec<f32>> {
+        (0..self.len())
+            .map(|index| self.at(index).expect("index is in range"))
+            .collect()
     }
 }
 
 impl ffi::VecVecString {
+    pub fn to_vec(&self) -> Vec<Vec<String>> {
+        (0..self.len())
+            .map(|index| self.at(index).expect("index is in range"))
+            .collect()
+    }
+
     pub fn new_unique_from(value: Vec<Vec<String>>) -> UniquePtr<ffi::VecVecString> {
         let mut v = ffi::new_vec_vec_string();
         v.pin_mut().reserve(value.len());
@@ -285,79 +677,3 @@ impl ffi::VecVecString {
         v
     }
 }
-
-pub struct Generator {
-    generator: UniquePtr<ffi::GeneratorWrapper>,
-}
-
-pub struct GenerateCallbackContext(Box<dyn Fn(GenerationStepResult) -> bool>);
-
-impl Generator {
-    pub fn new(
-        model_path: &str,
-        device: Device,
-        device_indicies: &[i32],
-        compute_type: ComputeType,
-        inter_threads: usize,
-        intra_threads: usize,
-        max_queued_batches: i32,
-    ) -> Result<Generator, CTranslate2Error> {
-        let generator = ffi::new_generator_wrapper(
-            model_path,
-            &device.to_string(),
-            device_indicies.to_vec(),
-            &compute_type.to_string(),
-            inter_threads,
-            intra_threads,
-            max_queued_batches,
-        )
-        .map_err(|ex| CTranslate2Error(ex))?;
-        Ok(Generator { generator })
-    }
-
-    pub fn device(&self) -> Result<Device, ParseError> {
-        self.generator.device().parse()
-    }
-
-    pub fn num_replicas(&self) -> usize {
-        self.generator.num_replicas()
-    }
-
-    pub fn num_queued_batches(&self) -> usize {
-        self.generator.num_queued_batches()
-    }
-
-    pub fn num_active_batches(&self) -> usize {
-        self.generator.num_active_batches()
-    }
-
-    pub fn generate_batch<F>(
-        &self,
-        tokens: Vec<Vec<String>>,
-        max_batch_size: usize,
-        batch_type: BatchType,
-        options: GenerationOptions,
-        callback: Option<F>
-    ) -> Result<Vec<GenerationResult>, CTranslate2Error> where F: Fn(GenerationStepResult) -> bool + 'static {
-        match callback {
-            Some(callback) => self.generator
-            .generate_batch_with_callback(
-                ffi::VecVecString::new_unique_from(tokens),
-                max_batch_size,
-                &batch_type.to_string(),
-                Box::new(options),
-                |result: GenerationStepResult, context: &GenerateCallbackContext| context.0(result),
-                Box::new(GenerateCallbackContext(Box::new(callback))),
-            )
-            .map_err(|ex| CTranslate2Error(ex)),
-            None => self.generator
-            .generate_batch(
-                ffi::VecVecString::new_unique_from(tokens),
-                max_batch_size,
-                &batch_type.to_string(),
-                Box::new(options),
-            )
-            .map_err(|ex| CTranslate2Error(ex))
-        }
-    }
-}
@@ -0,0 +1,138 @@
+//! Model loading shared by `Generator`, `Translator`, `Encoder` and `Whisper`.
+
+#[cfg(any(feature = "tar", feature = "zip"))]
+use crate::ArchiveModelReader;
+#[cfg(feature = "mmap")]
+use crate::MmapModelReader;
+use crate::{
+    ffi, CTranslate2Error, ComputeType, Device, ErrorContext, LoadSummary, ModelReader,
+    ModelReaderContext,
+};
+use cxx::memory::UniquePtrTarget;
+use cxx::UniquePtr;
+
+// C++ constructor of a wrapper from `model` (a directory, a Rust model reader or in-memory
+// files), followed by the arguments of `LoadOptions`.
+pub(crate) type Constructor<M, T> =
+    fn(M, &str, Vec<i32>, &str, usize, usize, i32) -> Result<UniquePtr<T>, cxx::Exception>;
+type FromPath<T> =
+    fn(&str, &str, Vec<i32>, &str, usize, usize, i32) -> Result<UniquePtr<T>, cxx::Exception>;
+
+// A C++ wrapper of a model loaded by `LoadOptions`.
+pub(crate) trait ModelWrapper: UniquePtrTarget + Sized {
+    const FROM_PATH: FromPath<Self>;
+    const FROM_READER: Constructor<Box<ModelReaderContext>, Self>;
+}
+
+impl ModelWrapper for ffi::GeneratorWrapper {
+    const FROM_PATH: FromPath<Self> = ffi::new_generator_wrapper;
+    const FROM_READER: Constructor<Box<ModelReaderContext>, Self> =
+        ffi::new_generator_wrapper_from_reader;
+}
+
+impl ModelWrapper for ffi::TranslatorWrapper {
+    const FROM_PATH: FromPath<Self> = ffi::new_translator_wrapper;
+    const FROM_READER: Constructor<Box<ModelReaderContext>, Self> =
+        ffi::new_translator_wrapper_from_reader;
+}
+
+impl ModelWrapper for ffi::EncoderWrapper {
+    const FROM_PATH: FromPath<Self> = ffi::new_encoder_wrapper;
+    const FROM_READER: Constructor<Box<ModelReaderContext>, Self> =
+        ffi::new_encoder_wrapper_from_reader;
+}
+
+impl ModelWrapper for ffi::WhisperWrapper {
+    const FROM_PATH: FromPath<Self> = ffi::new_whisper_wrapper;
+    const FROM_READER: Constructor<Box<ModelReaderContext>, Self> =
+        ffi::new_whisper_wrapper_from_reader;
+}
+
+pub(crate) struct LoadedModel<T: UniquePtrTarget> {
+    pub(crate) wrapper: UniquePtr<T>,
+    pub(crate) model_path: String,
+    pub(crate) load_summary: LoadSummary,
+}
+
+// Devices and threads of a model, as given to the constructors of the wrappers.
+pub(crate) struct LoadOptions<'a> {
+    pub(crate) device: Device,
+    pub(crate) device_indicies: &'a [i32],
+    pub(crate) compute_type: ComputeType,
+    pub(crate) inter_threads: usize,
+    pub(crate) intra_threads: usize,
+    pub(crate) max_queued_batches: i32,
+}
+
+impl LoadOptions<'_> {
+    // Loads the model directory at `model_path`, or an archive of it.
+    pub(crate) fn load_path<T: ModelWrapper>(
+        &self,
+        call: &'static str,
+        model_path: &str,
+    ) -> Result<LoadedModel<T>, CTranslate2Error> {
+        #[cfg(any(feature = "tar", feature = "zip"))]
+        if ArchiveModelReader::is_archive(model_path) {
+            let reader =
+                ArchiveModelReader::open(model_path).map_err(|source| CTranslate2Error::Io {
+                    context: ErrorContext::new(call, model_path),
+                    source,
+                })?;
+            return self.load_reader(call, reader);
+        }
+        self.load(call, model_path.to_string(), model_path, T::FROM_PATH)
+    }
+
+    pub(crate) fn load_reader<T: ModelWrapper>(
+        &self,
+        call: &'static str,
+        reader: impl ModelReader + 'static,
+    ) -> Result<LoadedModel<T>, CTranslate2Error> {
+        let model_path = reader.model_id();
+        let reader = Box::new(ModelReaderContext(Box::new(reader)));
+        self.load(call, model_path, reader, T::FROM_READER)
+    }
+
+    #[cfg(feature = "mmap")]
+    pub(crate) fn load_mmap<T: ModelWrapper>(
+        &self,
+        call: &'static str,
+        model_path: &str,
+    ) -> Result<LoadedModel<T>, CTranslate2Error> {
+        let reader = MmapModelReader::open(model_path).map_err(|source| CTranslate2Error::Io {
+            context: ErrorContext::new(call, model_path),
+            source,
+        })?;
+        self.load_reader(call, reader)
+    }
+
+    // Constructs the wrapper from `model`, measuring the load. `model_path` identifies the
+    // model in the errors.
+    pub(crate) fn load<M, T: UniquePtrTarget>(
+        &self,
+        call: &'static str,
+        model_path: String,
+        model: M,
+        construct: Constructor<M, T>,
+    ) -> Result<LoadedModel<T>, CTranslate2Error> {
+        let (wrapper, load_summary) = LoadSummary::measure(|| {
+            construct(
+                model,
+                &self.device.to_string(),
+                self.device_indicies.to_vec(),
+                &self.compute_type.to_string(),
+                self.inter_threads,
+                self.intra_threads,
+                self.max_queued_batches,
+            )
+            .map_err(|ex| {
+                CTranslate2Error::from_load_exception(ErrorContext::new(call, &model_path), ex)
+            })
+        })?;
+        Ok(LoadedModel {
+            wrapper,
+            model_path,
+            load_summary,
+        })
+    }
+}
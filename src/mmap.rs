@@ -0,0 +1,127 @@
+//! Models read through memory maps.
+
+use crate::ModelReader;
+use std::io::{self, Read};
+
+// Size of the consumed ranges given back to the kernel, a multiple of the page size.
+const RELEASE_SIZE: usize = 1 << 20;
+
+/// Reads the files of a model directory through memory maps. CTranslate2 copies the weights
+/// into its own buffers, so each range of a file is released once it has been copied: the
+/// file adds about 1 MiB to the resident memory instead of its whole size.
+pub struct MmapModelReader {
+    model_path: String,
+}
+
+impl MmapModelReader {
+    pub fn open(model_path: &str) -> io::Result<MmapModelReader> {
+        if !std::fs::metadata(model_path)?.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{model_path} is not a directory"),
+            ));
+        }
+        Ok(MmapModelReader {
+            model_path: model_path.to_string(),
+        })
+    }
+}
+
+impl ModelReader for MmapModelReader {
+    fn model_id(&self) -> String {
+        self.model_path.clone()
+    }
+
+    fn get_file(&self, filename: &str) -> io::Result<Option<Box<dyn Read + '_>>> {
+        let path = std::path::Path::new(&self.model_path).join(filename);
+        let file = match std::fs::File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        // Empty files cannot be mapped.
+        if file.metadata()?.len() == 0 {
+            return Ok(Some(Box::new(io::empty())));
+        }
+        // SAFETY: the model files must not be modified while the model is loading.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        Ok(Some(Box::new(MappedFile::new(map))))
+    }
+}
+
+// Copies a mapped file out sequentially, releasing the pages that were copied.
+struct MappedFile {
+    map: memmap2::Mmap,
+    position: usize,
+    released: usize,
+}
+
+impl MappedFile {
+    fn new(map: memmap2::Mmap) -> MappedFile {
+        #[cfg(unix)]
+        let _ = map.advise(memmap2::Advice::Sequential);
+        MappedFile {
+            map,
+            position: 0,
+            released: 0,
+        }
+    }
+
+    #[cfg(unix)]
+    fn release(&mut self) {
+        // Whole ranges only, the page holding `position` can still be read.
+        let end = match self.position == self.map.len() {
+            true => self.position,
+            false => self.position - self.position % RELEASE_SIZE,
+        };
+        if end > self.released {
+            // SAFETY: the released range lies before `position` and is never read again.
+            let _ = unsafe {
+                self.map.unchecked_advise_range(
+                    memmap2::UncheckedAdvice::DontNeed,
+                    self.released,
+                    end - self.released,
+                )
+            };
+            self.released = end;
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn release(&mut self) {}
+}
+
+impl Read for MappedFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let size = (&self.map[self.position..]).read(buf)?;
+        self.position += size;
+        self.release();
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mapped_files_are_read_before_being_released() {
+        let content: Vec<u8> = (0..3 * RELEASE_SIZE + 100).map(|i| i as u8).collect();
+        let mut map = memmap2::MmapMut::map_anon(content.len()).unwrap();
+        map.copy_from_slice(&content);
+        // Released pages of an anonymous map read back as zeros.
+        let mut file = MappedFile::new(map.make_read_only().unwrap());
+        let mut read = Vec::new();
+        let mut buf = vec![0; RELEASE_SIZE / 3];
+        loop {
+            let size = file.read(&mut buf).unwrap();
+            if size == 0 {
+                break;
+            }
+            read.extend_from_slice(&buf[..size]);
+        }
+        assert!(read == content);
+        #[cfg(unix)]
+        assert_eq!(file.released, content.len());
+    }
+}
@@ -0,0 +1,196 @@
+use crate::CTranslate2Error;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Source of the model files, for models that are not stored in a directory.
+///
+/// The files are requested by name (e.g. `model.bin`, `config.json`) while the model is
+/// loaded, possibly from several threads.
+pub trait ModelReader: Send + Sync {
+    /// Identifier of the model, used in error messages.
+    fn model_id(&self) -> String;
+
+    /// Opens a file of the model, or returns `None` if the model has no such file. CTranslate2
+    /// reads it in chunks while loading, so it does not need to fit in memory.
+    fn get_file(&self, filename: &str) -> io::Result<Option<Box<dyn Read + '_>>>;
+}
+
+pub struct ModelReaderContext(pub(crate) Box<dyn ModelReader>);
+
+impl ModelReaderContext {
+    pub(crate) fn model_id(&self) -> String {
+        self.0.model_id()
+    }
+
+    pub(crate) fn open_file(&self, filename: &str) -> io::Result<Box<ModelFileReader>> {
+        let source = match self.0.get_file(filename)? {
+            // SAFETY: the C++ stream reading the file keeps the reader context alive and drops
+            // the file first, and the boxed reader does not move while the context lives.
+            Some(file) => ModelFileSource::Read(unsafe {
+                std::mem::transmute::<Box<dyn Read + '_>, Box<dyn Read + 'static>>(file)
+            }),
+            None => ModelFileSource::Missing,
+        };
+        Ok(Box::new(ModelFileReader(source)))
+    }
+}
+
+enum ModelFileSource {
+    Missing,
+    Read(Box<dyn Read>),
+}
+
+/// A model file opened by `ModelReaderContext`, which CTranslate2 reads in chunks while the
+/// model loads instead of receiving it in full.
+pub struct ModelFileReader(ModelFileSource);
+
+impl ModelFileReader {
+    pub(crate) fn found(&self) -> bool {
+        !matches!(self.0, ModelFileSource::Missing)
+    }
+
+    // Fills the start of `buf`, returning 0 at the end of the file.
+    pub(crate) fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let ModelFileSource::Read(file) = &mut self.0 else {
+            return Ok(0);
+        };
+        loop {
+            match file.read(buf) {
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Time and memory used to load a model, see `Generator::load_summary`.
+///
+/// The memory figures are only measured after `LoadSummary::track_memory(true)`, and are read
+/// from `/proc/self/status` (`None` on other platforms). They cover the whole process, so
+/// loading several models concurrently skews them, and measuring the peak resets the
+/// process-wide `VmHWM` mark.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LoadSummary {
+    pub load_time: Duration,
+    /// Resident memory before loading, in bytes.
+    pub rss_before: Option<u64>,
+    /// Resident memory after loading, in bytes.
+    pub rss: Option<u64>,
+    /// Peak resident memory while loading, in bytes: the `VmHWM` mark, reset through
+    /// `/proc/self/clear_refs` before loading. `None` when the mark could not be reset.
+    pub peak_rss: Option<u64>,
+}
+
+static TRACK_MEMORY: AtomicBool = AtomicBool::new(false);
+
+impl LoadSummary {
+    /// Enables measuring the memory of the models loaded from now on, process-wide. Off by
+    /// default, since each load then resets the `VmHWM` mark that other code may rely on.
+    pub fn track_memory(enabled: bool) {
+        TRACK_MEMORY.store(enabled, Ordering::Relaxed);
+    }
+
+    pub(crate) fn measure<T>(
+        load: impl FnOnce() -> Result<T, CTranslate2Error>,
+    ) -> Result<(T, LoadSummary), CTranslate2Error> {
+        if !TRACK_MEMORY.load(Ordering::Relaxed) {
+            let start = Instant::now();
+            let loaded = load()?;
+            let summary = LoadSummary {
+                load_time: start.elapsed(),
+                ..Default::default()
+            };
+            return Ok((loaded, summary));
+        }
+        let rss_before = process_memory("VmRSS");
+        let peak_reset = reset_peak_memory();
+        let start = Instant::now();
+        let loaded = load()?;
+        let summary = LoadSummary {
+            load_time: start.elapsed(),
+            rss_before,
+            rss: process_memory("VmRSS"),
+            peak_rss: peak_reset.then(|| process_memory("VmHWM")).flatten(),
+        };
+        Ok((loaded, summary))
+    }
+}
+
+// Resets the peak resident memory (`VmHWM`) of the process to its current resident memory,
+// see proc(5).
+fn reset_peak_memory() -> bool {
+    std::fs::write("/proc/self/clear_refs", "5").is_ok()
+}
+
+// Reads a memory field of /proc/self/status, e.g. `VmRSS:   123456 kB`.
+fn process_memory(field: &str) -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status.lines().find_map(|line| {
+        let value = line.strip_prefix(field)?.strip_prefix(':')?;
+        let kb: u64 = value.trim().strip_suffix("kB")?.trim().parse().ok()?;
+        Some(kb * 1024)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestReader;
+
+    impl ModelReader for TestReader {
+        fn model_id(&self) -> String {
+            "test".to_string()
+        }
+
+        fn get_file(&self, filename: &str) -> io::Result<Option<Box<dyn Read + '_>>> {
+            Ok(match filename {
+                "config.json" => Some(Box::new(&b"{\"layers\": 2}"[..])),
+                _ => None,
+            })
+        }
+    }
+
+    #[test]
+    fn files_are_read_in_chunks() {
+        let context = ModelReaderContext(Box::new(TestReader));
+        let mut file = context.open_file("config.json").unwrap();
+        assert!(file.found());
+        let mut content = Vec::new();
+        let mut buf = [0; 4];
+        loop {
+            let size = file.read(&mut buf).unwrap();
+            if size == 0 {
+                break;
+            }
+            content.extend_from_slice(&buf[..size]);
+        }
+        assert_eq!(content, b"{\"layers\": 2}");
+    }
+
+    #[test]
+    fn missing_files() {
+        let context = ModelReaderContext(Box::new(TestReader));
+        let mut missing = context.open_file("vocabulary.txt").unwrap();
+        assert!(!missing.found());
+        assert_eq!(missing.read(&mut [0; 4]).unwrap(), 0);
+    }
+
+    #[test]
+    fn load_summary_measures_the_load() {
+        let (loaded, summary) = LoadSummary::measure(|| Ok(vec![1u8; 1 << 20])).unwrap();
+        assert_eq!(loaded.len(), 1 << 20);
+        assert_eq!(
+            (summary.rss_before, summary.rss, summary.peak_rss),
+            (None, None, None)
+        );
+
+        LoadSummary::track_memory(true);
+        let (_, summary) = LoadSummary::measure(|| Ok(vec![1u8; 1 << 20])).unwrap();
+        LoadSummary::track_memory(false);
+        if let (Some(rss), Some(peak_rss)) = (summary.rss, summary.peak_rss) {
+            assert!(peak_rss >= rss);
+        }
+    }
+}
@@ -0,0 +1,398 @@
+//! Reader of the CTranslate2 `model.bin` format, to inspect a model without loading it.
+//!
+//! The file starts with the binary version, the spec name and revision, followed by the
+//! variables (name, shape, data type and raw data) and, from version 3, the aliases. Only the
+//! headers are read, the variable data is skipped.
+
+use crate::DataType;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Latest binary version written by the CTranslate2 converters.
+pub const CURRENT_BINARY_VERSION: u32 = 6;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModelInfo {
+    pub binary_version: u32,
+    /// Name of the model specification, e.g. `TransformerDecoderModelSpec`. Empty for models
+    /// converted before binary version 2.
+    pub spec: String,
+    pub revision: u32,
+    pub variables: Vec<VariableInfo>,
+    /// Pairs of `(alias, variable name)` for variables shared under several names.
+    pub aliases: Vec<(String, String)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct VariableInfo {
+    pub name: String,
+    pub shape: Vec<usize>,
+    pub dtype: DataType,
+    /// Size of the data, in bytes.
+    pub num_bytes: u64,
+    /// Position of the data in the file.
+    pub offset: u64,
+}
+
+impl VariableInfo {
+    /// Number of elements of the variable, or `None` if it overflows `usize`.
+    pub fn num_elements(&self) -> Option<usize> {
+        self.shape
+            .iter()
+            .try_fold(1usize, |count, &dim| count.checked_mul(dim))
+    }
+}
+
+// Smallest header of a variable: an empty name, rank 0, the data type and the data size.
+const MIN_VARIABLE_HEADER_SIZE: u64 = 2 + 1 + 1 + 1 + 4;
+
+impl ModelInfo {
+    /// Reads the `model.bin` file of a model directory.
+    pub fn from_model_dir(model_path: impl AsRef<Path>) -> io::Result<ModelInfo> {
+        ModelInfo::from_file(model_path.as_ref().join("model.bin"))
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<ModelInfo> {
+        ModelInfo::read(BufReader::new(File::open(path)?))
+    }
+
+    pub fn read<R: Read + Seek>(mut reader: R) -> io::Result<ModelInfo> {
+        let start = reader.stream_position()?;
+        let end = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(start))?;
+
+        let binary_version = read_u32(&mut reader)?;
+        if binary_version > CURRENT_BINARY_VERSION {
+            return Err(invalid_data(format!(
+                "unsupported model binary version {binary_version} (at most \
+                 {CURRENT_BINARY_VERSION} is supported)"
+            )));
+        }
+        let (spec, revision) = if binary_version >= 2 {
+            (read_string(&mut reader)?, read_u32(&mut reader)?)
+        } else {
+            (String::new(), 1)
+        };
+
+        let num_variables = read_u32(&mut reader)?;
+        // The count is not trusted for the allocation: bound it by what the file can hold.
+        let remaining = end.saturating_sub(reader.stream_position()?);
+        let mut variables = Vec::with_capacity(
+            (num_variables as u64).min(remaining / MIN_VARIABLE_HEADER_SIZE) as usize,
+        );
+        for _ in 0..num_variables {
+            let name = read_string(&mut reader)?;
+            let rank = read_u8(&mut reader)?;
+            let shape = (0..rank)
+                .map(|_| read_u32(&mut reader).map(|dim| dim as usize))
+                .collect::<io::Result<Vec<_>>>()?;
+            // Before version 4, the data type is given by its item size and is followed by the
+            // number of elements instead of the number of bytes.
+            let (dtype, num_bytes) = if binary_version >= 4 {
+                let dtype = dtype_from_id(read_u8(&mut reader)?)?;
+                (dtype, read_u32(&mut reader)? as u64)
+            } else {
+                let item_size = read_u8(&mut reader)?;
+                let dtype = dtype_from_item_size(item_size)?;
+                (dtype, read_u32(&mut reader)? as u64 * item_size as u64)
+            };
+            let offset = reader.stream_position()?;
+            if offset + num_bytes > end {
+                return Err(invalid_data(format!(
+                    "the data of variable {name} is truncated"
+                )));
+            }
+            reader.seek(SeekFrom::Current(num_bytes as i64))?;
+            variables.push(VariableInfo {
+                name,
+                shape,
+                dtype,
+                num_bytes,
+                offset,
+            });
+        }
+
+        let mut aliases = Vec::new();
+        if binary_version >= 3 {
+            let num_aliases = read_u32(&mut reader)?;
+            for _ in 0..num_aliases {
+                let alias = read_string(&mut reader)?;
+                let variable = read_string(&mut reader)?;
+                aliases.push((alias, variable));
+            }
+        }
+
+        Ok(ModelInfo {
+            binary_version,
+            spec,
+            revision,
+            variables,
+            aliases,
+        })
+    }
+
+    /// Looks up a variable by name or alias.
+    pub fn variable(&self, name: &str) -> Option<&VariableInfo> {
+        let name = self
+            .aliases
+            .iter()
+            .find(|(alias, _)| alias == name)
+            .map_or(name, |(_, variable)| variable.as_str());
+        self.variables.iter().find(|variable| variable.name == name)
+    }
+
+    /// Total size of the variables, in bytes.
+    pub fn num_bytes(&self) -> u64 {
+        self.variables
+            .iter()
+            .map(|variable| variable.num_bytes)
+            .sum()
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// Same order as `ctranslate2::DataType`.
+fn dtype_from_id(id: u8) -> io::Result<DataType> {
+    match id {
+        0 => Ok(DataType::Float32),
+        1 => Ok(DataType::Int8),
+        2 => Ok(DataType::Int16),
+        3 => Ok(DataType::Int32),
+        4 => Ok(DataType::Float16),
+        5 => Ok(DataType::BFloat16),
+        _ => Err(invalid_data(format!("unknown data type id {id}"))),
+    }
+}
+
+// Binary versions before 4 only stored the item size.
+fn dtype_from_item_size(item_size: u8) -> io::Result<DataType> {
+    match item_size {
+        4 => Ok(DataType::Float32),
+        2 => Ok(DataType::Int16),
+        1 => Ok(DataType::Int8),
+        _ => Err(invalid_data(format!("unknown item size {item_size}"))),
+    }
+}
+
+fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    let mut buffer = [0; 1];
+    reader.read_exact(&mut buffer)?;
+    Ok(buffer[0])
+}
+
+fn read_u16(reader: &mut impl Read) -> io::Result<u16> {
+    let mut buffer = [0; 2];
+    reader.read_exact(&mut buffer)?;
+    Ok(u16::from_le_bytes(buffer))
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut buffer = [0; 4];
+    reader.read_exact(&mut buffer)?;
+    Ok(u32::from_le_bytes(buffer))
+}
+
+// Strings are stored with their length, which includes the null terminator.
+fn read_string(reader: &mut impl Read) -> io::Result<String> {
+    let length = read_u16(reader)? as usize;
+    let mut buffer = vec![0; length];
+    reader.read_exact(&mut buffer)?;
+    if buffer.pop() != Some(0) {
+        return Err(invalid_data("string is not null-terminated".to_string()));
+    }
+    String::from_utf8(buffer).map_err(|_| invalid_data("string is not valid UTF-8".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // Writes a `model.bin` file in the format of `binary_version`.
+    struct ModelWriter {
+        binary_version: u32,
+        buffer: Vec<u8>,
+    }
+
+    impl ModelWriter {
+        fn new(binary_version: u32, num_variables: u32) -> ModelWriter {
+            let mut writer = ModelWriter {
+                binary_version,
+                buffer: Vec::new(),
+            };
+            writer.u32(binary_version);
+            if binary_version >= 2 {
+                writer.string("TransformerDecoderModelSpec");
+                writer.u32(3);
+            }
+            writer.u32(num_variables);
+            writer
+        }
+
+        fn u32(&mut self, value: u32) {
+            self.buffer.extend_from_slice(&value.to_le_bytes());
+        }
+
+        fn string(&mut self, value: &str) {
+            self.buffer
+                .extend_from_slice(&(value.len() as u16 + 1).to_le_bytes());
+            self.buffer.extend_from_slice(value.as_bytes());
+            self.buffer.push(0);
+        }
+
+        // `dtype` is the data type id from version 4, and the item size before.
+        fn variable(&mut self, name: &str, shape: &[u32], dtype: u8, item_size: usize) {
+            self.string(name);
+            self.buffer.push(shape.len() as u8);
+            for &dim in shape {
+                self.u32(dim);
+            }
+            self.buffer.push(dtype);
+            let num_elements = shape.iter().product::<u32>();
+            if self.binary_version >= 4 {
+                self.u32(num_elements * item_size as u32);
+            } else {
+                self.u32(num_elements);
+            }
+            let num_bytes = num_elements as usize * item_size;
+            self.buffer.resize(self.buffer.len() + num_bytes, 0xab);
+        }
+
+        fn aliases(&mut self, aliases: &[(&str, &str)]) {
+            self.u32(aliases.len() as u32);
+            for (alias, variable) in aliases {
+                self.string(alias);
+                self.string(variable);
+            }
+        }
+
+        fn read(&self) -> io::Result<ModelInfo> {
+            ModelInfo::read(Cursor::new(&self.buffer))
+        }
+    }
+
+    #[test]
+    fn version_1_has_no_spec() {
+        let mut writer = ModelWriter::new(1, 1);
+        writer.variable("weight", &[2, 3], 4, 4);
+        let info = writer.read().unwrap();
+        assert_eq!(info.spec, "");
+        assert_eq!(info.revision, 1);
+        assert_eq!(info.variables[0].num_bytes, 24);
+    }
+
+    #[test]
+    fn version_2_stores_the_number_of_elements() {
+        let mut writer = ModelWriter::new(2, 2);
+        writer.variable("weight", &[2, 3], 4, 4);
+        writer.variable("scale", &[5], 2, 2);
+        let info = writer.read().unwrap();
+        assert_eq!(info.spec, "TransformerDecoderModelSpec");
+        assert_eq!(info.revision, 3);
+        assert!(info.aliases.is_empty());
+
+        let weight = &info.variables[0];
+        assert_eq!(weight.shape, [2, 3]);
+        assert_eq!(weight.dtype, DataType::Float32);
+        assert_eq!(weight.num_elements(), Some(6));
+        assert_eq!(weight.num_bytes, 24);
+        let scale = &info.variables[1];
+        assert_eq!(scale.dtype, DataType::Int16);
+        assert_eq!(scale.num_bytes, 10);
+        assert_eq!(scale.offset, weight.offset + 24 + 2 + 6 + 1 + 4 + 1 + 4);
+        assert_eq!(info.num_bytes(), 34);
+    }
+
+    #[test]
+    fn version_3_reads_the_aliases() {
+        let mut writer = ModelWriter::new(3, 1);
+        writer.variable("decoder/embeddings/weight", &[4, 2], 1, 1);
+        writer.aliases(&[("decoder/projection/weight", "decoder/embeddings/weight")]);
+        let info = writer.read().unwrap();
+        assert_eq!(info.variables[0].dtype, DataType::Int8);
+        assert_eq!(info.variables[0].num_bytes, 8);
+        assert_eq!(
+            info.aliases,
+            [(
+                "decoder/projection/weight".to_string(),
+                "decoder/embeddings/weight".to_string()
+            )]
+        );
+        let variable = info.variable("decoder/projection/weight").unwrap();
+        assert_eq!(variable.name, "decoder/embeddings/weight");
+        assert!(info.variable("decoder/missing").is_none());
+    }
+
+    #[test]
+    fn version_4_stores_the_data_type_and_size() {
+        let mut writer = ModelWriter::new(CURRENT_BINARY_VERSION, 2);
+        writer.variable("weight", &[2, 2], 4, 2);
+        writer.variable("bias", &[2], 5, 2);
+        writer.aliases(&[]);
+        let info = writer.read().unwrap();
+        assert_eq!(info.variables[0].dtype, DataType::Float16);
+        assert_eq!(info.variables[0].num_bytes, 8);
+        assert_eq!(info.variables[1].dtype, DataType::BFloat16);
+        assert_eq!(info.variables[1].num_bytes, 4);
+    }
+
+    #[test]
+    fn truncated_files_are_rejected() {
+        let mut writer = ModelWriter::new(CURRENT_BINARY_VERSION, 1);
+        writer.variable("weight", &[4], 0, 4);
+        writer.aliases(&[]);
+
+        // In the data of the variable.
+        let error = ModelInfo::read(Cursor::new(&writer.buffer[..writer.buffer.len() - 8]));
+        assert_eq!(error.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        // In its header.
+        let error = ModelInfo::read(Cursor::new(&writer.buffer[..48]));
+        assert_eq!(error.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn huge_variable_counts_are_not_allocated() {
+        let writer = ModelWriter::new(CURRENT_BINARY_VERSION, u32::MAX);
+        let error = writer.read().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn unknown_data_types_are_rejected() {
+        let mut writer = ModelWriter::new(CURRENT_BINARY_VERSION, 1);
+        writer.variable("weight", &[1], 9, 4);
+        assert_eq!(
+            writer.read().unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        let mut writer = ModelWriter::new(3, 1);
+        writer.variable("weight", &[1], 3, 3);
+        assert_eq!(
+            writer.read().unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        let writer = ModelWriter::new(CURRENT_BINARY_VERSION + 1, 0);
+        assert_eq!(
+            writer.read().unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn num_elements_does_not_overflow() {
+        let variable = VariableInfo {
+            name: "weight".to_string(),
+            shape: vec![usize::MAX, 2],
+            dtype: DataType::Float32,
+            num_bytes: 0,
+            offset: 0,
+        };
+        assert_eq!(variable.num_elements(), None);
+    }
+}
@@ -0,0 +1,160 @@
+use crate::{CTranslate2Error, GenerateBatchFuture, GenerationStepResult};
+use futures_core::Stream;
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+
+// Number of steps buffered by `generate_tokens` before the generation waits for the consumer.
+const STEP_CHANNEL_CAPACITY: usize = 8;
+
+struct StepChannelState {
+    steps: VecDeque<GenerationStepResult>,
+    // Set when the generation is done and no more steps will be sent.
+    closed: bool,
+    // Set when the receiving iterator or stream is dropped.
+    dropped: bool,
+    waker: Option<Waker>,
+}
+
+pub(crate) struct StepChannel {
+    state: Mutex<StepChannelState>,
+    condvar: Condvar,
+}
+
+impl StepChannel {
+    pub(crate) fn new() -> Arc<StepChannel> {
+        Arc::new(StepChannel {
+            state: Mutex::new(StepChannelState {
+                steps: VecDeque::new(),
+                closed: false,
+                dropped: false,
+                waker: None,
+            }),
+            condvar: Condvar::new(),
+        })
+    }
+}
+
+pub(crate) struct StepSender(pub(crate) Arc<StepChannel>);
+
+impl StepSender {
+    // Blocks the generation while the buffer is full. Returns false once the receiver is gone,
+    // which stops the decoding.
+    pub(crate) fn send(&self, step: GenerationStepResult) -> bool {
+        let mut state = self.0.state.lock().unwrap();
+        while state.steps.len() >= STEP_CHANNEL_CAPACITY && !state.dropped {
+            state = self.0.condvar.wait(state).unwrap();
+        }
+        if state.dropped {
+            return false;
+        }
+        state.steps.push_back(step);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        self.0.condvar.notify_all();
+        true
+    }
+}
+
+impl Drop for StepSender {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap();
+        state.closed = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        self.0.condvar.notify_all();
+    }
+}
+
+pub(crate) struct StepReceiver {
+    channel: Arc<StepChannel>,
+    result: GenerateBatchFuture,
+}
+
+impl StepReceiver {
+    pub(crate) fn new(channel: Arc<StepChannel>, result: GenerateBatchFuture) -> StepReceiver {
+        StepReceiver { channel, result }
+    }
+
+    fn take_error(&mut self) -> Option<CTranslate2Error> {
+        if let Some(error) = self.result.error.take() {
+            return Some(error);
+        }
+        let mut state = self.result.state.lock().unwrap();
+        match state.results.first_mut().and_then(|result| result.take()) {
+            Some(Err(error)) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl Drop for StepReceiver {
+    fn drop(&mut self) {
+        let mut state = self.channel.state.lock().unwrap();
+        state.dropped = true;
+        state.steps.clear();
+        self.channel.condvar.notify_all();
+    }
+}
+
+/// Blocking iterator over the tokens produced by `Generator::generate_tokens`.
+/// Dropping the iterator stops the generation.
+pub struct GenerationStepIterator(pub(crate) StepReceiver);
+
+impl GenerationStepIterator {
+    /// Returns the error that ended the generation early, if any.
+    pub fn take_error(&mut self) -> Option<CTranslate2Error> {
+        self.0.take_error()
+    }
+}
+
+impl Iterator for GenerationStepIterator {
+    type Item = GenerationStepResult;
+
+    fn next(&mut self) -> Option<GenerationStepResult> {
+        let channel = &self.0.channel;
+        let mut state = channel.state.lock().unwrap();
+        loop {
+            if let Some(step) = state.steps.pop_front() {
+                channel.condvar.notify_all();
+                return Some(step);
+            }
+            if state.closed {
+                return None;
+            }
+            state = channel.condvar.wait(state).unwrap();
+        }
+    }
+}
+
+/// Asynchronous stream over the tokens produced by `Generator::generate_tokens_stream`.
+/// Dropping the stream stops the generation.
+pub struct GenerationStepStream(pub(crate) StepReceiver);
+
+impl GenerationStepStream {
+    /// Returns the error that ended the generation early, if any.
+    pub fn take_error(&mut self) -> Option<CTranslate2Error> {
+        self.0.take_error()
+    }
+}
+
+impl Stream for GenerationStepStream {
+    type Item = GenerationStepResult;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<GenerationStepResult>> {
+        let channel = &self.get_mut().0.channel;
+        let mut state = channel.state.lock().unwrap();
+        if let Some(step) = state.steps.pop_front() {
+            channel.condvar.notify_all();
+            return Poll::Ready(Some(step));
+        }
+        if state.closed {
+            return Poll::Ready(None);
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
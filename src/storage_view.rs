@@ -0,0 +1,74 @@
+use crate::{ffi, CTranslate2Error, DataType, Device, ErrorContext, ParseError};
+use cxx::UniquePtr;
+
+/// A tensor owned by CTranslate2, such as the logits returned by `Generator::forward_batch`.
+pub struct StorageView {
+    pub(crate) view: UniquePtr<ffi::StorageViewWrapper>,
+}
+
+impl StorageView {
+    /// Creates a float32 storage on the CPU from row-major values.
+    pub fn new(shape: &[usize], data: Vec<f32>) -> Result<StorageView, CTranslate2Error> {
+        let view = ffi::new_storage_view(shape, data).map_err(|ex| {
+            CTranslate2Error::from_exception(
+                ErrorContext {
+                    call: "StorageView::new",
+                    model_path: None,
+                },
+                ex,
+            )
+        })?;
+        Ok(StorageView { view })
+    }
+
+    #[cfg(feature = "ndarray")]
+    pub fn from_ndarray<S, D>(
+        array: &ndarray::ArrayBase<S, D>,
+    ) -> Result<StorageView, CTranslate2Error>
+    where
+        S: ndarray::Data<Elem = f32>,
+        D: ndarray::Dimension,
+    {
+        StorageView::new(array.shape(), array.iter().copied().collect())
+    }
+
+    pub fn shape(&self) -> Vec<usize> {
+        self.view.shape()
+    }
+
+    pub fn dtype(&self) -> Result<DataType, ParseError> {
+        self.view.dtype().parse()
+    }
+
+    pub fn device(&self) -> Result<Device, ParseError> {
+        self.view.device().parse()
+    }
+
+    /// Copies the values to the host as a flat, row-major `Vec<f32>`.
+    /// Float16 storages are converted to float32.
+    pub fn to_vec(&self) -> Result<Vec<f32>, CTranslate2Error> {
+        self.view.to_vec_f32().map_err(|ex| {
+            CTranslate2Error::from_exception(
+                ErrorContext {
+                    call: "StorageView::to_vec",
+                    model_path: None,
+                },
+                ex,
+            )
+        })
+    }
+
+    #[cfg(feature = "ndarray")]
+    pub fn to_ndarray(&self) -> Result<ndarray::ArrayD<f32>, CTranslate2Error> {
+        let values = self.to_vec()?;
+        ndarray::ArrayD::from_shape_vec(ndarray::IxDyn(&self.shape()), values).map_err(|source| {
+            CTranslate2Error::Shape {
+                context: ErrorContext {
+                    call: "StorageView::to_ndarray",
+                    model_path: None,
+                },
+                source,
+            }
+        })
+    }
+}
@@ -0,0 +1,73 @@
+//! Fixtures shared by the unit tests.
+
+use crate::{ffi, ErrorContext, GenerationStepResult};
+#[cfg(any(feature = "tar", feature = "zip", feature = "encryption"))]
+use std::path::{Path, PathBuf};
+
+pub(crate) fn context() -> ErrorContext {
+    ErrorContext {
+        call: "test",
+        model_path: None,
+    }
+}
+
+// First step of the first hypothesis of the first example, as reported by CTranslate2.
+pub(crate) fn ffi_step() -> ffi::GenerationStepResult {
+    ffi::GenerationStepResult {
+        step: 0,
+        batch_id: 0,
+        input_index: 0,
+        hypothesis_id: 0,
+        token_id: 1,
+        token: "a".to_string(),
+        log_prob: -0.5,
+        log_prob_valid: true,
+        is_last: false,
+    }
+}
+
+// The same step as given to the Rust callbacks, without log probabilities.
+pub(crate) fn step() -> GenerationStepResult {
+    GenerationStepResult {
+        step: 0,
+        batch_id: 0,
+        input_index: 0,
+        hypothesis_id: 0,
+        token_id: 1,
+        token: "a".to_string(),
+        log_prob: None,
+        cumulative_log_prob: None,
+        is_last: false,
+    }
+}
+
+// A fresh directory for the files of a test, removed with its content when dropped.
+#[cfg(any(feature = "tar", feature = "zip", feature = "encryption"))]
+pub(crate) struct TempDir(PathBuf);
+
+#[cfg(any(feature = "tar", feature = "zip", feature = "encryption"))]
+impl TempDir {
+    pub(crate) fn new(name: &str) -> TempDir {
+        let dir =
+            std::env::temp_dir().join(format!("ctranslate2-rs-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+}
+
+#[cfg(any(feature = "tar", feature = "zip", feature = "encryption"))]
+impl std::ops::Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(any(feature = "tar", feature = "zip", feature = "encryption"))]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
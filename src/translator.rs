@@ -0,0 +1,373 @@
+use crate::loader::{LoadOptions, LoadedModel};
+use crate::{
+    ffi, BatchType, CTranslate2Error, ComputeType, Device, ErrorContext, LoadSummary, ModelReader,
+    ParseError, ScoringOptions, ScoringResult,
+};
+use cxx::UniquePtr;
+
+/// Decoding options of `Translator`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct TranslationOptions {
+    /// Beam size to use for beam search (set 1 to run greedy search).
+    pub beam_size: usize,
+    /// Beam search patience factor, as described in https://arxiv.org/abs/2204.05424.
+    /// The decoding will continue until beam_size*patience hypotheses are finished.
+    pub patience: f32,
+    /// Exponential penalty applied to the length during beam search.
+    /// The scores are normalized with:
+    ///   hypothesis_score /= (hypothesis_length ** length_penalty)
+    pub length_penalty: f32,
+    /// Coverage penalty weight applied during beam search.
+    pub coverage_penalty: f32,
+    /// Penalty applied to the score of previously generated tokens, as described in
+    /// https://arxiv.org/abs/1909.05858 (set > 1 to penalize).
+    pub repetition_penalty: f32,
+    /// Prevent repetitions of ngrams with this size (set 0 to disable).
+    pub no_repeat_ngram_size: usize,
+    /// Disable the generation of the unknown token.
+    pub disable_unk: bool,
+    /// Disable the generation of some sequences of tokens.
+    pub suppress_sequences: Vec<Vec<String>>,
+    /// Biases decoding towards a given prefix, see https://arxiv.org/abs/1912.03393 --section 4.2
+    /// Only activates biased-decoding when beta is in range (0, 1) and SearchStrategy is set to BeamSearch.
+    /// The closer beta is to 1, the stronger the bias is towards the given prefix.
+    ///
+    /// If beta <= 0 and a non-empty prefix is given, then the prefix will be used as a
+    /// hard-prefix rather than a soft, biased-prefix.
+    pub prefix_bias_beta: f32,
+    /// Stop the decoding on one of these tokens
+    pub end_token: Vec<String>,
+    /// If end_token is empty, top on the EOS token
+    pub empty_end_token_means_stop_on_eos_token: bool,
+    /// Include the end token in the result.
+    pub return_end_token: bool,
+    /// Truncate the inputs after this many tokens (set 0 to disable truncation).
+    pub max_input_length: usize,
+    /// Decoding length constraints.
+    pub max_decoding_length: usize,
+    pub min_decoding_length: usize,
+    /// Randomly sample from the top K candidates (set 0 to sample from the full output distribution).
+    pub sampling_topk: usize,
+    /// Keep the most probable tokens whose cumulative probability exceeds this value.
+    pub sampling_topp: f32,
+    /// High temperature increase randomness.
+    pub sampling_temperature: f32,
+    /// Allow using the vocabulary map included in the model directory, if it exists.
+    pub use_vmap: bool,
+    /// Number of hypotheses to include in the result.
+    pub num_hypotheses: usize,
+    /// Include scores in the result.
+    pub return_scores: bool,
+    /// Include the attention vectors of the last decoder layer in the result.
+    pub return_attention: bool,
+    /// Return alternatives at the first unconstrained decoding position. This is typically
+    /// used with a target prefix to provide alternatives at a specifc location in the
+    /// translation.
+    pub return_alternatives: bool,
+    /// Minimum probability to expand an alternative.
+    pub min_alternative_expansion_prob: f32,
+    /// Replace unknown target tokens by the original source token with the highest attention.
+    pub replace_unknowns: bool,
+}
+
+impl Default for TranslationOptions {
+    fn default() -> TranslationOptions {
+        TranslationOptions {
+            beam_size: 2,
+            patience: 1.,
+            length_penalty: 1.,
+            coverage_penalty: 0.,
+            repetition_penalty: 1.,
+            no_repeat_ngram_size: 0,
+            disable_unk: false,
+            suppress_sequences: Vec::new(),
+            prefix_bias_beta: 0.,
+            end_token: Vec::new(),
+            empty_end_token_means_stop_on_eos_token: true,
+            return_end_token: false,
+            max_input_length: 1024,
+            max_decoding_length: 256,
+            min_decoding_length: 1,
+            sampling_topk: 1,
+            sampling_topp: 1.,
+            sampling_temperature: 1.,
+            use_vmap: false,
+            num_hypotheses: 1,
+            return_scores: false,
+            return_attention: false,
+            return_alternatives: false,
+            min_alternative_expansion_prob: 0.,
+            replace_unknowns: false,
+        }
+    }
+}
+
+impl TranslationOptions {
+    pub(crate) fn into_ffi(self) -> ffi::TranslationOptions {
+        ffi::TranslationOptions {
+            beam_size: self.beam_size,
+            patience: self.patience,
+            length_penalty: self.length_penalty,
+            coverage_penalty: self.coverage_penalty,
+            repetition_penalty: self.repetition_penalty,
+            no_repeat_ngram_size: self.no_repeat_ngram_size,
+            disable_unk: self.disable_unk,
+            suppress_sequences: ffi::VecVecString::new_unique_from(self.suppress_sequences),
+            prefix_bias_beta: self.prefix_bias_beta,
+            end_token: self.end_token,
+            empty_end_token_means_stop_on_eos_token: self.empty_end_token_means_stop_on_eos_token,
+            return_end_token: self.return_end_token,
+            max_input_length: self.max_input_length,
+            max_decoding_length: self.max_decoding_length,
+            min_decoding_length: self.min_decoding_length,
+            sampling_topk: self.sampling_topk,
+            sampling_topp: self.sampling_topp,
+            sampling_temperature: self.sampling_temperature,
+            use_vmap: self.use_vmap,
+            num_hypotheses: self.num_hypotheses,
+            return_scores: self.return_scores,
+            return_attention: self.return_attention,
+            return_alternatives: self.return_alternatives,
+            min_alternative_expansion_prob: self.min_alternative_expansion_prob,
+            replace_unknowns: self.replace_unknowns,
+        }
+    }
+}
+
+/// Attention of a translation over the source tokens: one row per target token.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AttentionMatrix {
+    pub rows: Vec<Vec<f32>>,
+}
+
+/// Translations of one example, from the best to the worst.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TranslationResult {
+    pub hypotheses: Vec<Vec<String>>,
+    /// Score of each hypothesis, when `return_scores` is set.
+    pub scores: Vec<f32>,
+    /// Attention of each hypothesis, when `return_attention` is set.
+    pub attention: Vec<AttentionMatrix>,
+}
+
+impl TranslationResult {
+    pub(crate) fn from_ffi(result: ffi::TranslationResult) -> TranslationResult {
+        TranslationResult {
+            hypotheses: result.hypotheses.to_vec(),
+            scores: result.scores,
+            attention: result
+                .attention
+                .into_iter()
+                .map(|matrix| AttentionMatrix {
+                    rows: matrix.rows.to_vec(),
+                })
+                .collect(),
+        }
+    }
+}
+
+pub struct Translator {
+    translator: UniquePtr<ffi::TranslatorWrapper>,
+    model_path: String,
+    load_summary: LoadSummary,
+}
+
+impl Translator {
+    pub fn new(
+        model_path: &str,
+        device: Device,
+        device_indicies: &[i32],
+        compute_type: ComputeType,
+        inter_threads: usize,
+        intra_threads: usize,
+        max_queued_batches: i32,
+    ) -> Result<Translator, CTranslate2Error> {
+        LoadOptions {
+            device,
+            device_indicies,
+            compute_type,
+            inter_threads,
+            intra_threads,
+            max_queued_batches,
+        }
+        .load_path("Translator::new", model_path)
+        .map(Translator::from_loaded)
+    }
+
+    /// Loads a model whose files are provided by a custom `ModelReader`.
+    pub fn from_reader<R: ModelReader + 'static>(
+        reader: R,
+        device: Device,
+        device_indicies: &[i32],
+        compute_type: ComputeType,
+        inter_threads: usize,
+        intra_threads: usize,
+        max_queued_batches: i32,
+    ) -> Result<Translator, CTranslate2Error> {
+        LoadOptions {
+            device,
+            device_indicies,
+            compute_type,
+            inter_threads,
+            intra_threads,
+            max_queued_batches,
+        }
+        .load_reader("Translator::from_reader", reader)
+        .map(Translator::from_loaded)
+    }
+
+    /// Same as `Translator::new`, but the model files are read through memory maps, see
+    /// `MmapModelReader`.
+    #[cfg(feature = "mmap")]
+    pub fn from_mmap(
+        model_path: &str,
+        device: Device,
+        device_indicies: &[i32],
+        compute_type: ComputeType,
+        inter_threads: usize,
+        intra_threads: usize,
+        max_queued_batches: i32,
+    ) -> Result<Translator, CTranslate2Error> {
+        LoadOptions {
+            device,
+            device_indicies,
+            compute_type,
+            inter_threads,
+            intra_threads,
+            max_queued_batches,
+        }
+        .load_mmap("Translator::from_mmap", model_path)
+        .map(Translator::from_loaded)
+    }
+
+    fn from_loaded(model: LoadedModel<ffi::TranslatorWrapper>) -> Translator {
+        Translator {
+            translator: model.wrapper,
+            model_path: model.model_path,
+            load_summary: model.load_summary,
+        }
+    }
+
+    /// Time and memory used to load the model, see `LoadSummary`.
+    pub fn load_summary(&self) -> &LoadSummary {
+        &self.load_summary
+    }
+
+    fn context(&self, call: &'static str) -> ErrorContext {
+        ErrorContext::new(call, &self.model_path)
+    }
+
+    pub fn device(&self) -> Result<Device, ParseError> {
+        self.translator.device().parse()
+    }
+
+    pub fn num_replicas(&self) -> usize {
+        self.translator.num_replicas()
+    }
+
+    pub fn num_queued_batches(&self) -> usize {
+        self.translator.num_queued_batches()
+    }
+
+    pub fn num_active_batches(&self) -> usize {
+        self.translator.num_active_batches()
+    }
+
+    pub fn translate_batch(
+        &self,
+        source: Vec<Vec<String>>,
+        target_prefix: Option<Vec<Vec<String>>>,
+        max_batch_size: usize,
+        batch_type: BatchType,
+        options: TranslationOptions,
+    ) -> Result<Vec<TranslationResult>, CTranslate2Error> {
+        self.translator
+            .translate_batch(
+                ffi::VecVecString::new_unique_from(source),
+                match target_prefix {
+                    Some(target_prefix) => ffi::VecVecString::new_unique_from(target_prefix),
+                    None => UniquePtr::null(),
+                },
+                max_batch_size,
+                &batch_type.to_string(),
+                Box::new(options.into_ffi()),
+            )
+            .map(|results| {
+                results
+                    .into_iter()
+                    .map(TranslationResult::from_ffi)
+                    .collect()
+            })
+            .map_err(|ex| {
+                CTranslate2Error::from_exception(self.context("Translator::translate_batch"), ex)
+            })
+    }
+
+    pub fn score_batch(
+        &self,
+        source: Vec<Vec<String>>,
+        target: Vec<Vec<String>>,
+        max_batch_size: usize,
+        batch_type: BatchType,
+        options: ScoringOptions,
+    ) -> Result<Vec<ScoringResult>, CTranslate2Error> {
+        self.translator
+            .score_batch(
+                ffi::VecVecString::new_unique_from(source),
+                ffi::VecVecString::new_unique_from(target),
+                max_batch_size,
+                &batch_type.to_string(),
+                Box::new(options),
+            )
+            .map_err(|ex| {
+                CTranslate2Error::from_exception(self.context("Translator::score_batch"), ex)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_are_converted_to_ffi() {
+        let options = TranslationOptions {
+            beam_size: 4,
+            suppress_sequences: vec![vec!["a".to_string(), "b".to_string()]],
+            replace_unknowns: true,
+            ..Default::default()
+        }
+        .into_ffi();
+        assert_eq!(options.beam_size, 4);
+        assert_eq!(
+            options.suppress_sequences.to_vec(),
+            vec![vec!["a".to_string(), "b".to_string()]]
+        );
+        assert!(options.replace_unknowns);
+    }
+
+    #[test]
+    fn results_are_converted_from_ffi() {
+        let mut rows = ffi::new_vec_vec_f32();
+        rows.pin_mut().push_back(vec![0.25, 0.75]);
+        let result = TranslationResult::from_ffi(ffi::TranslationResult {
+            hypotheses: ffi::VecVecString::new_unique_from(vec![vec!["hallo".to_string()]]),
+            scores: vec![-0.5],
+            attention: vec![ffi::AttentionMatrix { rows }],
+        });
+        assert_eq!(
+            result,
+            TranslationResult {
+                hypotheses: vec![vec!["hallo".to_string()]],
+                scores: vec![-0.5],
+                attention: vec![AttentionMatrix {
+                    rows: vec![vec![0.25, 0.75]],
+                }],
+            }
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use crate::ParseError;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Copy, Clone, Debug)]
+pub enum Device {
+    CPU,
+    CUDA,
+}
+
+impl fmt::Display for Device {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Device::CPU => "cpu",
+            Device::CUDA => "cuda",
+        })
+    }
+}
+
+impl FromStr for Device {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "cpu" => Ok(Device::CPU),
+            "cuda" => Ok(Device::CUDA),
+            _ => Err(ParseError(format!("Unknown device {s}"))),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum ComputeType {
+    Default,
+    Auto,
+    Float32,
+    Int8,
+    Int8Float16,
+    Int16,
+    Float16,
+}
+
+impl fmt::Display for ComputeType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ComputeType::Default => "default",
+            ComputeType::Auto => "auto",
+            ComputeType::Float32 => "float32",
+            ComputeType::Int8 => "int8",
+            ComputeType::Int8Float16 => "int8_float16",
+            ComputeType::Int16 => "int16",
+            ComputeType::Float16 => "float16",
+        })
+    }
+}
+
+impl FromStr for ComputeType {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "default" => Ok(ComputeType::Default),
+            "auto" => Ok(ComputeType::Auto),
+            "float32" | "float" => Ok(ComputeType::Float32),
+            "int8" => Ok(ComputeType::Int8),
+            "int8_float16" => Ok(ComputeType::Int8Float16),
+            "float16" => Ok(ComputeType::Float16),
+            _ => Err(ParseError(format!("Unknown compute type {s}"))),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum BatchType {
+    Examples,
+    Tokens,
+}
+
+impl fmt::Display for BatchType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BatchType::Examples => "examples",
+            BatchType::Tokens => "tokens",
+        })
+    }
+}
+
+impl FromStr for BatchType {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "examples" => Ok(BatchType::Examples),
+            "tokens" => Ok(BatchType::Tokens),
+            _ => Err(ParseError(format!("Unknown batch type {s}"))),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DataType {
+    Float32,
+    Int8,
+    Int16,
+    Int32,
+    Float16,
+    BFloat16,
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DataType::Float32 => "float32",
+            DataType::Int8 => "int8",
+            DataType::Int16 => "int16",
+            DataType::Int32 => "int32",
+            DataType::Float16 => "float16",
+            DataType::BFloat16 => "bfloat16",
+        })
+    }
+}
+
+impl FromStr for DataType {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "float32" | "float" => Ok(DataType::Float32),
+            "int8" => Ok(DataType::Int8),
+            "int16" => Ok(DataType::Int16),
+            "int32" => Ok(DataType::Int32),
+            "float16" => Ok(DataType::Float16),
+            "bfloat16" => Ok(DataType::BFloat16),
+            _ => Err(ParseError(format!("Unknown data type {s}"))),
+        }
+    }
+}
@@ -0,0 +1,346 @@
+use crate::loader::{LoadOptions, LoadedModel};
+use crate::{
+    ffi, CTranslate2Error, ComputeType, Device, ErrorContext, LoadSummary, ModelReader, ParseError,
+    StorageView,
+};
+use cxx::UniquePtr;
+
+/// Decoding options of `Whisper::generate`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct WhisperOptions {
+    /// Beam size to use for beam search (set 1 to run greedy search).
+    pub beam_size: usize,
+    /// Beam search patience factor, as described in https://arxiv.org/abs/2204.05424.
+    /// The decoding will continue until beam_size*patience hypotheses are finished.
+    pub patience: f32,
+    /// Exponential penalty applied to the length during beam search.
+    pub length_penalty: f32,
+    /// Penalty applied to the score of previously generated tokens, as described in
+    /// https://arxiv.org/abs/1909.05858 (set > 1 to penalize).
+    pub repetition_penalty: f32,
+    /// Prevent repetitions of ngrams with this size (set 0 to disable).
+    pub no_repeat_ngram_size: usize,
+    /// Maximum generation length.
+    pub max_length: usize,
+    /// Randomly sample from the top K candidates (set 0 to sample from the full distribution).
+    pub sampling_topk: usize,
+    /// High temperatures increase randomness.
+    pub sampling_temperature: f32,
+    /// Number of hypotheses to include in the result.
+    pub num_hypotheses: usize,
+    /// Include scores in the result.
+    pub return_scores: bool,
+    /// Include the probability of the no speech token in the result.
+    pub return_no_speech_prob: bool,
+    /// Maximum index of the first predicted timestamp.
+    pub max_initial_timestamp_index: usize,
+    /// Suppress blank outputs at the beginning of the sampling.
+    pub suppress_blank: bool,
+    /// List of token IDs to suppress.
+    /// -1 will suppress a default set of symbols as defined in the model config.json file.
+    pub suppress_tokens: Vec<i32>,
+}
+
+impl Default for WhisperOptions {
+    fn default() -> WhisperOptions {
+        WhisperOptions {
+            beam_size: 5,
+            patience: 1.,
+            length_penalty: 1.,
+            repetition_penalty: 1.,
+            no_repeat_ngram_size: 0,
+            max_length: 448,
+            sampling_topk: 1,
+            sampling_temperature: 1.,
+            num_hypotheses: 1,
+            return_scores: false,
+            return_no_speech_prob: false,
+            max_initial_timestamp_index: 50,
+            suppress_blank: true,
+            suppress_tokens: vec![-1],
+        }
+    }
+}
+
+impl WhisperOptions {
+    pub(crate) fn into_ffi(self) -> ffi::WhisperOptions {
+        ffi::WhisperOptions {
+            beam_size: self.beam_size,
+            patience: self.patience,
+            length_penalty: self.length_penalty,
+            repetition_penalty: self.repetition_penalty,
+            no_repeat_ngram_size: self.no_repeat_ngram_size,
+            max_length: self.max_length,
+            sampling_topk: self.sampling_topk,
+            sampling_temperature: self.sampling_temperature,
+            num_hypotheses: self.num_hypotheses,
+            return_scores: self.return_scores,
+            return_no_speech_prob: self.return_no_speech_prob,
+            max_initial_timestamp_index: self.max_initial_timestamp_index,
+            suppress_blank: self.suppress_blank,
+            suppress_tokens: self.suppress_tokens,
+        }
+    }
+}
+
+/// Transcriptions of one item of the features, from the best to the worst.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WhisperGenerationResult {
+    pub sequences: Vec<Vec<String>>,
+    pub sequence_ids: Vec<Vec<usize>>,
+    /// Score of each sequence, when `return_scores` is set.
+    pub scores: Vec<f32>,
+    /// Probability of the no speech token, when `return_no_speech_prob` is set.
+    pub no_speech_prob: f32,
+}
+
+impl WhisperGenerationResult {
+    pub(crate) fn from_ffi(result: ffi::WhisperGenerationResult) -> WhisperGenerationResult {
+        WhisperGenerationResult {
+            sequences: result.sequences.to_vec(),
+            sequence_ids: result.sequence_ids.to_vec(),
+            scores: result.scores,
+            no_speech_prob: result.no_speech_prob,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WhisperAlignmentResult {
+    /// Pairs of `(text_index, time_index)` along the alignment path.
+    pub alignments: Vec<(usize, usize)>,
+    /// Probability of each text token.
+    pub text_token_probs: Vec<f32>,
+}
+
+pub struct Whisper {
+    whisper: UniquePtr<ffi::WhisperWrapper>,
+    model_path: String,
+    load_summary: LoadSummary,
+}
+
+impl Whisper {
+    pub fn new(
+        model_path: &str,
+        device: Device,
+        device_indicies: &[i32],
+        compute_type: ComputeType,
+        inter_threads: usize,
+        intra_threads: usize,
+        max_queued_batches: i32,
+    ) -> Result<Whisper, CTranslate2Error> {
+        LoadOptions {
+            device,
+            device_indicies,
+            compute_type,
+            inter_threads,
+            intra_threads,
+            max_queued_batches,
+        }
+        .load_path("Whisper::new", model_path)
+        .map(Whisper::from_loaded)
+    }
+
+    /// Loads a model whose files are provided by a custom `ModelReader`.
+    pub fn from_reader<R: ModelReader + 'static>(
+        reader: R,
+        device: Device,
+        device_indicies: &[i32],
+        compute_type: ComputeType,
+        inter_threads: usize,
+        intra_threads: usize,
+        max_queued_batches: i32,
+    ) -> Result<Whisper, CTranslate2Error> {
+        LoadOptions {
+            device,
+            device_indicies,
+            compute_type,
+            inter_threads,
+            intra_threads,
+            max_queued_batches,
+        }
+        .load_reader("Whisper::from_reader", reader)
+        .map(Whisper::from_loaded)
+    }
+
+    /// Same as `Whisper::new`, but the model files are read through memory maps, see
+    /// `MmapModelReader`.
+    #[cfg(feature = "mmap")]
+    pub fn from_mmap(
+        model_path: &str,
+        device: Device,
+        device_indicies: &[i32],
+        compute_type: ComputeType,
+        inter_threads: usize,
+        intra_threads: usize,
+        max_queued_batches: i32,
+    ) -> Result<Whisper, CTranslate2Error> {
+        LoadOptions {
+            device,
+            device_indicies,
+            compute_type,
+            inter_threads,
+            intra_threads,
+            max_queued_batches,
+        }
+        .load_mmap("Whisper::from_mmap", model_path)
+        .map(Whisper::from_loaded)
+    }
+
+    fn from_loaded(model: LoadedModel<ffi::WhisperWrapper>) -> Whisper {
+        Whisper {
+            whisper: model.wrapper,
+            model_path: model.model_path,
+            load_summary: model.load_summary,
+        }
+    }
+
+    /// Time and memory used to load the model, see `LoadSummary`.
+    pub fn load_summary(&self) -> &LoadSummary {
+        &self.load_summary
+    }
+
+    fn context(&self, call: &'static str) -> ErrorContext {
+        ErrorContext::new(call, &self.model_path)
+    }
+
+    pub fn device(&self) -> Result<Device, ParseError> {
+        self.whisper.device().parse()
+    }
+
+    pub fn num_replicas(&self) -> usize {
+        self.whisper.num_replicas()
+    }
+
+    pub fn num_queued_batches(&self) -> usize {
+        self.whisper.num_queued_batches()
+    }
+
+    pub fn num_active_batches(&self) -> usize {
+        self.whisper.num_active_batches()
+    }
+
+    pub fn is_multilingual(&self) -> bool {
+        self.whisper.is_multilingual()
+    }
+
+    /// Transcribes the mel spectrograms in `features` (shape `[batch_size, n_mels, chunk_length]`),
+    /// continuing each prompt in `prompts` (e.g. `<|startoftranscript|>`, `<|en|>`, `<|transcribe|>`).
+    pub fn generate(
+        &self,
+        features: &StorageView,
+        prompts: Vec<Vec<String>>,
+        options: WhisperOptions,
+    ) -> Result<Vec<WhisperGenerationResult>, CTranslate2Error> {
+        self.whisper
+            .generate(
+                &features.view,
+                ffi::VecVecString::new_unique_from(prompts),
+                Box::new(options.into_ffi()),
+            )
+            .map(|results| {
+                results
+                    .into_iter()
+                    .map(WhisperGenerationResult::from_ffi)
+                    .collect()
+            })
+            .map_err(|ex| CTranslate2Error::from_exception(self.context("Whisper::generate"), ex))
+    }
+
+    /// Returns, for each item in `features`, the language tokens (e.g. `<|en|>`) and their
+    /// probabilities, sorted from the most to the least probable.
+    pub fn detect_language(
+        &self,
+        features: &StorageView,
+    ) -> Result<Vec<Vec<(String, f32)>>, CTranslate2Error> {
+        let results = self.whisper.detect_language(&features.view).map_err(|ex| {
+            CTranslate2Error::from_exception(self.context("Whisper::detect_language"), ex)
+        })?;
+        Ok(results
+            .into_iter()
+            .map(|result| {
+                result
+                    .languages
+                    .into_iter()
+                    .zip(result.probabilities)
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// Computes the alignments between the text tokens and the audio frames using the
+    /// cross-attention weights, as used to produce word-level timestamps.
+    /// `num_frames` is the number of (non-padding) frames in the features and
+    /// `median_filter_width` the width of the filter applied to the attention weights.
+    pub fn align(
+        &self,
+        features: &StorageView,
+        start_sequence: &[usize],
+        text_tokens: Vec<Vec<usize>>,
+        num_frames: usize,
+        median_filter_width: usize,
+    ) -> Result<Vec<WhisperAlignmentResult>, CTranslate2Error> {
+        let results = self
+            .whisper
+            .align(
+                &features.view,
+                start_sequence,
+                ffi::VecVecUsize::new_unique_from(text_tokens),
+                num_frames,
+                median_filter_width,
+            )
+            .map_err(|ex| CTranslate2Error::from_exception(self.context("Whisper::align"), ex))?;
+        Ok(results
+            .into_iter()
+            .map(|result| WhisperAlignmentResult {
+                alignments: result
+                    .text_indices
+                    .into_iter()
+                    .zip(result.time_indices)
+                    .collect(),
+                text_token_probs: result.text_token_probs,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_are_converted_to_ffi() {
+        let options = WhisperOptions {
+            beam_size: 1,
+            suppress_tokens: vec![-1, 50257],
+            return_no_speech_prob: true,
+            ..Default::default()
+        }
+        .into_ffi();
+        assert_eq!(options.beam_size, 1);
+        assert_eq!(options.suppress_tokens, vec![-1, 50257]);
+        assert!(options.return_no_speech_prob);
+    }
+
+    #[test]
+    fn results_are_converted_from_ffi() {
+        let result = WhisperGenerationResult::from_ffi(ffi::WhisperGenerationResult {
+            sequences: ffi::VecVecString::new_unique_from(vec![vec![" Hello".to_string()]]),
+            sequence_ids: ffi::VecVecUsize::new_unique_from(vec![vec![2425]]),
+            scores: vec![-0.5],
+            no_speech_prob: 0.125,
+        });
+        assert_eq!(
+            result,
+            WhisperGenerationResult {
+                sequences: vec![vec![" Hello".to_string()]],
+                sequence_ids: vec![vec![2425]],
+                scores: vec![-0.5],
+                no_speech_prob: 0.125,
+            }
+        );
+    }
+}